@@ -1,30 +1,35 @@
 // ACE Framework - Agentic Context Engineering
+#![allow(dead_code)]
 use crate::functional_core::*;
 use crate::imperative_shell::*;
 use crate::tools::*;
 use crate::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 
 pub struct ACEGenerator {
-    pub client: OllamaClient,
+    pub client: Arc<dyn LlmBackend>,
 }
 
 impl ACEGenerator {
-    pub fn new(client: OllamaClient) -> Self {
+    pub fn new(client: Arc<dyn LlmBackend>) -> Self {
         Self { client }
     }
 
-    #[allow(unused)]
-    pub async fn generate_trajectory(
-        &self,
-        query: &str,
-        context: &ContextState,
-    ) -> Result<Trajectory> {
-        let bullets = get_relevant_bullets(context, query, 10);
-        let _context_text = build_context_prompt(&bullets);
+    /// Answers `query` as a trajectory, with `bullets` as learned context.
+    pub async fn generate_trajectory(&self, query: &str, bullets: &[ContextBullet]) -> Result<Trajectory> {
+        let task = if bullets.is_empty() {
+            query.to_string()
+        } else {
+            format!("Relevant context:\n{}\n\n{}", build_context_prompt(bullets, false), query)
+        };
 
         let prompt = format!(
             "{}\n\nProvide a brief answer in this format:\nSTEPS: [step1; step2; step3]\nOUTCOME: your answer here\nSUCCESS: true\nUSED_BULLETS: []",
-            query
+            task
         );
 
         let response = self.client.generate(&prompt).await?;
@@ -32,13 +37,70 @@ impl ACEGenerator {
     }
 }
 
+#[async_trait]
+pub trait InsightExtractor: Send + Sync {
+    async fn extract(&self, response: &str, source_id: String) -> Vec<Insight>;
+}
+
+/// Parses `[Content: ...; Type: ...; Confidence: ...]` blocks.
+pub struct RegexExtractor;
+
+#[async_trait]
+impl InsightExtractor for RegexExtractor {
+    async fn extract(&self, response: &str, source_id: String) -> Vec<Insight> {
+        parse_insights_response(response, source_id)
+    }
+}
+
+/// Treats every non-empty line of the response as one insight.
+pub struct LineExtractor;
+
+#[async_trait]
+impl InsightExtractor for LineExtractor {
+    async fn extract(&self, response: &str, source_id: String) -> Vec<Insight> {
+        response
+            .lines()
+            .map(|l| l.trim().trim_start_matches(['-', '*', '•']).trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| Insight {
+                content: l.to_string(),
+                insight_type: "observation".to_string(),
+                confidence: 0.6,
+                source_id: source_id.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Asks a model to restate free-form text in the regex-parsable insight format.
+pub struct LlmExtractor(pub Box<dyn LlmBackend>);
+
+#[async_trait]
+impl InsightExtractor for LlmExtractor {
+    async fn extract(&self, response: &str, source_id: String) -> Vec<Insight> {
+        let prompt = format!(
+            "Rewrite the key learnings from this text as insights, one per line, in the format:\n[Content: the insight; Type: strategy|fact|pitfall; Confidence: 0.0-1.0]\n\nText:\n{}",
+            response
+        );
+        match self.0.generate(&prompt).await {
+            Ok(formatted) => parse_insights_response(&formatted, source_id),
+            Err(_) => parse_insights_response(response, source_id),
+        }
+    }
+}
+
 pub struct ACEReflector {
-    pub client: OllamaClient,
+    pub client: Arc<dyn LlmBackend>,
+    pub extractor: Box<dyn InsightExtractor>,
 }
 
 impl ACEReflector {
-    pub fn new(client: OllamaClient) -> Self {
-        Self { client }
+    pub fn new(client: Arc<dyn LlmBackend>) -> Self {
+        Self::with_extractor(client, Box::new(RegexExtractor))
+    }
+
+    pub fn with_extractor(client: Arc<dyn LlmBackend>, extractor: Box<dyn InsightExtractor>) -> Self {
+        Self { client, extractor }
     }
 
     #[allow(unused)]
@@ -56,189 +118,1808 @@ impl ACEReflector {
         );
 
         let response = self.client.generate(&prompt).await?;
-        Ok(parse_insights_response(&response, trajectory.query.clone()))
+        Ok(self.extractor.extract(&response, trajectory.query.clone()).await)
+    }
+
+    pub async fn reflect_deduped(
+        &self,
+        trajectory: &Trajectory,
+        dedup: &mut InsightDeduplicator,
+    ) -> Result<Vec<Insight>> {
+        let insights = self.reflect(trajectory).await?;
+        Ok(insights.into_iter().filter(|insight| dedup.insert(insight)).collect())
     }
 }
 
-pub struct ACECurator {
+/// A summary bullet and the ids of the bullets it replaces.
+pub type CompactionSummary = (Vec<String>, ContextBullet);
+
+/// One LLM summary per `(tag, bullets)` group, carrying the group's combined
+/// feedback.
+pub async fn summarize_groups(
+    client: &dyn LlmBackend,
+    groups: &[(String, Vec<ContextBullet>)],
+) -> Result<Vec<CompactionSummary>> {
+    let mut summaries = Vec::new();
+    for (tag, group) in groups {
+        let listing: Vec<String> = group.iter().map(|b| format!("- {}", b.content)).collect();
+        let prompt = format!(
+            "Summarize these notes about '{}' into one concise insight that keeps every distinct fact:\n{}\n\nSummary:",
+            tag,
+            listing.join("\n")
+        );
+        let summary = client.generate(&prompt).await?;
+        let mut bullet = create_bullet(summary.trim().to_string(), vec![tag.clone()]);
+        bullet.helpful_count = group.iter().map(|b| b.helpful_count).sum();
+        bullet.harmful_count = group.iter().map(|b| b.harmful_count).sum();
+        bullet
+            .metadata
+            .insert("compacted_from".to_string(), serde_json::Value::from(group.len()));
+        summaries.push((group.iter().map(|b| b.id.clone()).collect(), bullet));
+    }
+    Ok(summaries)
+}
+
+/// Rates responses with a judge model, usually a smaller one than the
+/// generator.
+pub struct QualityAssessor {
+    pub client: Arc<dyn LlmBackend>,
+}
+
+impl QualityAssessor {
+    pub fn new(client: Arc<dyn LlmBackend>) -> Self {
+        Self { client }
+    }
+
+    pub async fn assess(&self, query: &str, response: &str) -> Result<QualityAssessment> {
+        let prompt = format!(
+            "Question: {}\nResponse: {}\n\nRate this response 1-5 for accuracy, completeness, and conciseness. Format: ACCURACY:N COMPLETENESS:N CONCISENESS:N",
+            query, response
+        );
+        let rating = self.client.generate(&prompt).await?;
+        parse_quality_assessment(&rating)
+            .ok_or_else(|| format!("Parse error: unreadable quality rating '{}'", rating.trim()))
+    }
+}
+
+/// Recent trajectories kept for the session so unfinished multi-step tasks
+/// can be listed and resumed. Oldest entries are dropped past `max_size`.
+#[derive(Debug, Clone)]
+pub struct TrajectoryStore {
+    pub store: Vec<Trajectory>,
+    pub max_size: usize,
+}
+
+impl TrajectoryStore {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            store: Vec::new(),
+            max_size,
+        }
+    }
+
+    pub fn push(&mut self, trajectory: Trajectory) {
+        self.store.push(trajectory);
+        if self.store.len() > self.max_size {
+            let excess = self.store.len() - self.max_size;
+            self.store.drain(..excess);
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Trajectory> {
+        self.store.iter().find(|t| t.id == id)
+    }
+
+    /// Newest first.
+    pub fn recent(&self, n: usize) -> Vec<&Trajectory> {
+        self.store.iter().rev().take(n).collect()
+    }
+
+    pub fn in_progress(&self) -> Vec<&Trajectory> {
+        self.store.iter().filter(|t| !t.success).collect()
+    }
+
+    fn replace(&mut self, id: &str, trajectory: Trajectory) {
+        if let Some(slot) = self.store.iter_mut().find(|t| t.id == id) {
+            *slot = trajectory;
+        }
+    }
+}
+
+impl Default for TrajectoryStore {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+/// Remembers insights already produced this session so repeated reflections
+/// on similar trajectories don't store the same lesson twice.
+#[derive(Debug, Clone, Default)]
+pub struct InsightDeduplicator {
+    pub seen_fingerprints: HashSet<u64>,
+}
+
+impl InsightDeduplicator {
+    const BASE: u64 = 1_000_003;
+
+    /// Polynomial hash over the normalized terms, so case and punctuation
+    /// differences map to the same fingerprint.
+    pub fn fingerprint(content: &str) -> u64 {
+        tokenize_terms(content)
+            .join(" ")
+            .bytes()
+            .fold(0u64, |hash, byte| hash.wrapping_mul(Self::BASE).wrapping_add(byte as u64))
+    }
+
+    /// Returns false if an insight with the same fingerprint was seen before.
+    pub fn insert(&mut self, insight: &Insight) -> bool {
+        self.seen_fingerprints.insert(Self::fingerprint(&insight.content))
+    }
+}
+
+// Storage behind the curator; swap in another implementation for tests or
+// alternative persistence.
+pub trait ContextStore: Send + Sync {
+    fn get_context(&self) -> &ContextState;
+
+    fn set_context(&mut self, context: ContextState);
+
+    fn apply_delta(&mut self, delta: &DeltaUpdate) {
+        let merged = merge_delta(self.get_context(), delta);
+        self.set_context(merged);
+    }
+
+    /// Writes the context to durable storage, for stores that have any.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct InMemoryContextStore {
     context: ContextState,
 }
 
-impl ACECurator {
-    pub fn new() -> Self {
+impl Default for InMemoryContextStore {
+    fn default() -> Self {
         Self {
             context: ContextState::new(),
         }
     }
+}
+
+impl ContextStore for InMemoryContextStore {
+    fn get_context(&self) -> &ContextState {
+        &self.context
+    }
+
+    fn set_context(&mut self, context: ContextState) {
+        self.context = context;
+    }
+}
+
+/// Wraps a store so every bullet it receives is keyed by `content_id`;
+/// content that is already stored is not inserted again.
+pub struct ContentAddressedStorage {
+    inner: Box<dyn ContextStore>,
+}
+
+impl ContentAddressedStorage {
+    pub fn new(inner: Box<dyn ContextStore>) -> Self {
+        Self { inner }
+    }
+}
+
+impl ContextStore for ContentAddressedStorage {
+    fn get_context(&self) -> &ContextState {
+        self.inner.get_context()
+    }
+
+    fn set_context(&mut self, context: ContextState) {
+        self.inner.set_context(context);
+    }
+
+    fn apply_delta(&mut self, delta: &DeltaUpdate) {
+        let addressed = content_address_delta(self.get_context(), delta);
+        if !addressed.bullets.is_empty() {
+            self.inner.apply_delta(&addressed);
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub struct ACECurator {
+    pub config: CuratorConfig,
+    store: Box<dyn ContextStore>,
+    /// Bullets taken out of the active context; never searched or prompted.
+    pub archive: HashMap<String, ArchivedBullet>,
+    stop_words: HashSet<String>,
+    deltas_applied: usize,
+    compaction_pending: bool,
+}
+
+impl ACECurator {
+    /// Stop-words are re-derived and archival runs every this many deltas.
+    const MAINTENANCE_INTERVAL: usize = 5;
+
+    pub fn new() -> Self {
+        Self::with_store(Box::new(InMemoryContextStore::default()))
+    }
+
+    pub fn with_store(store: Box<dyn ContextStore>) -> Self {
+        let mut curator = Self {
+            config: CuratorConfig::default(),
+            store,
+            archive: HashMap::new(),
+            stop_words: HashSet::new(),
+            deltas_applied: 0,
+            compaction_pending: false,
+        };
+        curator.refresh_stop_words();
+        curator
+    }
+
+    /// English stop-words plus tokens that are ubiquitous in the learned context.
+    pub fn stop_words(&self) -> &HashSet<String> {
+        &self.stop_words
+    }
+
+    fn refresh_stop_words(&mut self) {
+        let mut stop_words =
+            derive_corpus_stop_words(&self.get_context().bullets, CORPUS_STOP_WORD_FRACTION);
+        stop_words.extend(ENGLISH_STOP_WORDS.iter().map(|s| s.to_string()));
+        self.stop_words = stop_words;
+    }
 
     #[allow(unused)]
     pub fn create_delta(&self, insights: Vec<Insight>) -> DeltaUpdate {
         insights_to_delta(insights)
     }
 
-    #[allow(unused)]
-    pub fn apply_delta(&mut self, delta: &DeltaUpdate) {
-        self.context = merge_delta(&self.context, delta);
+    /// Moves bullets older than `age` whose helpful-minus-harmful score is
+    /// below `min_score` into the archive. Returns how many were moved.
+    pub fn run_archival(&mut self, age: chrono::Duration, min_score: i32) -> usize {
+        let now = chrono::Utc::now();
+        let mut context = self.get_context().clone();
+        let stale: Vec<String> = context
+            .bullets
+            .values()
+            .filter(|b| !b.pinned && now - b.created_at > age && b.helpful_count - b.harmful_count < min_score)
+            .map(|b| b.id.clone())
+            .collect();
+        if stale.is_empty() {
+            return 0;
+        }
+
+        for id in &stale {
+            if let Some(bullet) = context.bullets.remove(id) {
+                let reason = format!(
+                    "Older than {} days with score {}",
+                    age.num_days(),
+                    bullet.helpful_count - bullet.harmful_count
+                );
+                self.archive.insert(
+                    id.clone(),
+                    ArchivedBullet {
+                        bullet,
+                        archived_at: now,
+                        reason,
+                    },
+                );
+            }
+        }
+        context.version.increment();
+        self.set_context(context);
+        stale.len()
     }
 
-    pub fn get_context(&self) -> &ContextState {
-        &self.context
+    pub fn restore_from_archive(&mut self, bullet_id: &str) -> Result<()> {
+        let archived = self
+            .archive
+            .remove(bullet_id)
+            .ok_or_else(|| format!("No archived bullet with id {}", bullet_id))?;
+        let mut context = self.get_context().clone();
+        context.bullets.insert(bullet_id.to_string(), archived.bullet);
+        context.version.increment();
+        self.set_context(context);
+        Ok(())
+    }
+
+    /// Imports pre-curated bullets from a JSONL seed file, pinned so they
+    /// survive eviction. Returns how many were imported.
+    pub fn warm_from_seed(&mut self, path: &std::path::Path) -> Result<usize> {
+        let seeds = load_bullets_jsonl(path)?;
+        let mut context = self.get_context().clone();
+        for mut bullet in seeds.iter().cloned() {
+            bullet.pinned = true;
+            context.bullets.insert(bullet.id.clone(), bullet);
+        }
+        context.version.increment();
+        self.set_context(context);
+        self.refresh_stop_words();
+        Ok(seeds.len())
+    }
+
+    /// Full id of the single bullet whose id starts with `prefix`.
+    pub fn resolve_id_prefix(&self, prefix: &str) -> Result<String> {
+        let matches: Vec<&String> = self.get_context().bullets.keys().filter(|id| id.starts_with(prefix)).collect();
+        match matches.as_slice() {
+            [id] => Ok((*id).clone()),
+            [] => Err(format!("No bullet with id starting with {}", prefix)),
+            _ => Err(format!("{} bullets match {}; use a longer prefix", matches.len(), prefix)),
+        }
+    }
+
+    pub fn edit_bullet(&mut self, id: &str, new_content: &str) -> Result<()> {
+        self.update_bullet(id, |bullet, depth| Ok(edit_bullet_content(bullet, new_content, depth)))
+    }
+
+    pub fn rollback_bullet(&mut self, id: &str, steps: usize) -> Result<()> {
+        self.update_bullet(id, |bullet, _| rollback_bullet_content(bullet, steps))
+    }
+
+    fn update_bullet(
+        &mut self,
+        id: &str,
+        update: impl FnOnce(&ContextBullet, usize) -> Result<ContextBullet>,
+    ) -> Result<()> {
+        let mut context = self.get_context().clone();
+        let bullet = context
+            .bullets
+            .get(id)
+            .ok_or_else(|| format!("No bullet with id {}", id))?;
+        let updated = update(bullet, self.config.history_depth)?;
+        context.bullets.insert(id.to_string(), updated);
+        context.version.increment();
+        self.set_context(context);
+        Ok(())
+    }
+
+    pub fn pin_bullet(&mut self, id: &str) -> Result<()> {
+        self.set_pinned(id, true)
+    }
+
+    pub fn unpin_bullet(&mut self, id: &str) -> Result<()> {
+        self.set_pinned(id, false)
+    }
+
+    fn set_pinned(&mut self, id: &str, pinned: bool) -> Result<()> {
+        let mut context = self.get_context().clone();
+        let bullet = context
+            .bullets
+            .get_mut(id)
+            .ok_or_else(|| format!("No bullet with id {}", id))?;
+        bullet.pinned = pinned;
+        context.version.increment();
+        self.set_context(context);
+        Ok(())
+    }
+
+    /// Applies `CuratorConfig::max_bullets`. Returns how many were evicted.
+    pub fn run_eviction(&mut self) -> usize {
+        let Some(max_bullets) = self.config.max_bullets else {
+            return 0;
+        };
+        let (context, evicted) = evict_bullets(self.get_context(), max_bullets, self.config.eviction_strategy);
+        if !evicted.is_empty() {
+            self.set_context(context);
+        }
+        evicted.len()
+    }
+
+    /// Whether the context holds more than `COMPACTION_THRESHOLD` of
+    /// `max_bullets`.
+    pub fn needs_compaction(&self) -> bool {
+        self.config
+            .max_bullets
+            .is_some_and(|max| self.get_context().bullets.len() as f64 > COMPACTION_THRESHOLD * max as f64)
+    }
+
+    /// Set by `apply_delta` when `auto_compact` is on and the threshold was
+    /// crossed; summarizing needs the LLM, so the framework runs it.
+    pub fn compaction_pending(&self) -> bool {
+        self.compaction_pending
+    }
+
+    /// Replaces each group of two or more unpinned bullets sharing a top tag
+    /// with one LLM-written summary carrying the group's combined feedback.
+    /// The context is only changed once every group has been summarized.
+    pub async fn compact_via_summarization(&mut self, client: &dyn LlmBackend) -> Result<()> {
+        let groups = self.take_compaction_groups();
+        let summaries = summarize_groups(client, &groups).await?;
+        self.apply_compaction(summaries);
+        Ok(())
+    }
+
+    /// Groups of two or more unpinned bullets sharing a top tag, copied out
+    /// so they can be summarized without holding the curator. Clears the
+    /// pending flag.
+    pub fn take_compaction_groups(&mut self) -> Vec<(String, Vec<ContextBullet>)> {
+        self.compaction_pending = false;
+        group_by_top_tag(self.get_context())
+            .into_iter()
+            .filter(|(_, group)| group.len() > 1)
+            .map(|(tag, group)| (tag, group.into_iter().cloned().collect()))
+            .collect()
+    }
+
+    /// Swaps in each summary whose source bullets all still exist; groups
+    /// that changed while being summarized are left alone.
+    pub fn apply_compaction(&mut self, summaries: Vec<CompactionSummary>) {
+        let mut context = self.get_context().clone();
+        let (mut compacted, mut created) = (0, 0);
+        for (ids, bullet) in summaries {
+            if !ids.iter().all(|id| context.bullets.contains_key(id)) {
+                continue;
+            }
+            for id in &ids {
+                context.bullets.remove(id);
+            }
+            context.bullets.insert(bullet.id.clone(), bullet);
+            compacted += ids.len();
+            created += 1;
+        }
+        if created == 0 {
+            return;
+        }
+        context.version.increment();
+        self.set_context(context);
+        self.refresh_stop_words();
+        log_info(&format!("Compacted {} bullets → {} summaries.", compacted, created));
+    }
+
+    /// Counts one helpful or harmful rating on each bullet in `ids`.
+    /// Returns how many of them still exist.
+    pub fn record_feedback(&mut self, ids: &[String], helpful: bool) -> usize {
+        let mut context = self.get_context().clone();
+        let mut rated = 0;
+        for id in ids {
+            if let Some(bullet) = context.bullets.get(id) {
+                let updated = update_bullet_feedback(bullet, helpful);
+                context.bullets.insert(id.clone(), updated);
+                rated += 1;
+            }
+        }
+        if rated > 0 {
+            context.version.increment();
+            self.set_context(context);
+        }
+        rated
+    }
+
+    /// Counts a retrieval of each bullet, for LFU eviction.
+    pub fn record_access(&mut self, ids: &[String]) {
+        if ids.is_empty() {
+            return;
+        }
+        let mut context = self.get_context().clone();
+        for id in ids {
+            if let Some(bullet) = context.bullets.get_mut(id) {
+                bullet.access_count += 1;
+            }
+        }
+        self.set_context(context);
+    }
+
+    /// Drops bullets whose TTL has passed. Returns how many were removed.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = chrono::Utc::now();
+        let mut context = self.get_context().clone();
+        let before = context.bullets.len();
+        context.bullets.retain(|_, bullet| !bullet.is_expired(now));
+        let purged = before - context.bullets.len();
+        if purged > 0 {
+            context.version.increment();
+            self.set_context(context);
+        }
+        purged
+    }
+
+    pub fn get_health(&self) -> ContextHealth {
+        compute_context_health_weighted(self.get_context(), &self.config.health_weights)
+    }
+
+    pub fn find_near_duplicates(&self, threshold: f64) -> Vec<DuplicatePair> {
+        find_near_duplicates(&self.get_context().bullets, threshold)
+    }
+
+    pub fn remove_bullet(&mut self, id: &str) -> Option<ContextBullet> {
+        let mut context = self.get_context().clone();
+        let removed = context.bullets.remove(id)?;
+        context.version.increment();
+        self.set_context(context);
+        Some(removed)
+    }
+
+    /// Of two near-duplicates, the one to drop: lower helpful-minus-harmful
+    /// score, or the newer bullet when scores tie.
+    pub fn weaker_of<'a>(&'a self, pair: &'a DuplicatePair) -> Option<&'a str> {
+        let bullets = &self.get_context().bullets;
+        let (a, b) = (bullets.get(&pair.bullet_a_id)?, bullets.get(&pair.bullet_b_id)?);
+        let score = |bullet: &ContextBullet| bullet.helpful_count - bullet.harmful_count;
+        let drop_a = (score(a), std::cmp::Reverse(a.created_at)) < (score(b), std::cmp::Reverse(b.created_at));
+        Some(if drop_a { &pair.bullet_a_id } else { &pair.bullet_b_id })
     }
 
     pub fn get_stats(&self) -> ContextStats {
-        let helpful = self
-            .context
+        let context = self.get_context();
+        let helpful = context
             .bullets
             .values()
             .filter(|b| b.helpful_count > b.harmful_count)
             .count();
 
-        let avg_helpfulness = if self.context.bullets.is_empty() {
+        let avg_helpfulness = if context.bullets.is_empty() {
             0.0
         } else {
-            self.context
+            context
                 .bullets
                 .values()
                 .map(|b| b.helpful_count as f64)
                 .sum::<f64>()
-                / self.context.bullets.len() as f64
+                / context.bullets.len() as f64
         };
 
+        let mut tag_stats: HashMap<String, TagStats> = HashMap::new();
+        for bullet in context.bullets.values() {
+            for tag in &bullet.tags {
+                let stats = tag_stats.entry(tag.clone()).or_default();
+                stats.bullet_count += 1;
+                stats.avg_helpfulness += bullet.helpful_count as f64;
+                stats.total_uses += (bullet.helpful_count + bullet.harmful_count).max(0) as usize;
+            }
+        }
+        for stats in tag_stats.values_mut() {
+            stats.avg_helpfulness /= stats.bullet_count as f64;
+        }
+
         ContextStats {
-            total_bullets: self.context.bullets.len(),
+            total_bullets: context.bullets.len(),
             helpful_bullets: helpful,
-            version: self.context.version,
+            pinned_bullets: context.bullets.values().filter(|b| b.pinned).count(),
+            version: context.version.total(),
             avg_helpfulness,
+            tag_stats,
+            quality_warning: false,
+            tool_invocations: HashMap::new(),
+            avg_tool_latency: HashMap::new(),
+            recent_success_rate: 1.0,
+            low_success_alarm: false,
         }
     }
 }
 
+impl ContextStore for ACECurator {
+    fn get_context(&self) -> &ContextState {
+        self.store.get_context()
+    }
+
+    fn set_context(&mut self, context: ContextState) {
+        self.store.set_context(context);
+        record_emergency_snapshot(self.get_context());
+    }
+
+    fn apply_delta(&mut self, delta: &DeltaUpdate) {
+        self.purge_expired();
+        match self.config.addressing {
+            BulletAddressing::Uuid => self.store.apply_delta(delta),
+            BulletAddressing::ContentHash => {
+                let addressed = content_address_delta(self.get_context(), delta);
+                if !addressed.bullets.is_empty() {
+                    self.store.apply_delta(&addressed);
+                }
+            }
+        }
+        if self.config.auto_compact && self.needs_compaction() {
+            self.compaction_pending = true;
+        }
+        self.run_eviction();
+        self.deltas_applied += 1;
+        if self.deltas_applied.is_multiple_of(Self::MAINTENANCE_INTERVAL) {
+            if let Some(age) = self.config.archive_after {
+                self.run_archival(age, self.config.archive_min_score);
+            }
+            self.refresh_stop_words();
+        }
+        record_emergency_snapshot(self.get_context());
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.store.flush()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TagStats {
+    pub bullet_count: usize,
+    pub avg_helpfulness: f64,
+    /// Helpful plus harmful feedback across the tag's bullets.
+    pub total_uses: usize,
+}
+
 pub struct ContextStats {
     pub total_bullets: usize,
     pub helpful_bullets: usize,
-    pub version: i32,
+    pub pinned_bullets: usize,
+    pub version: u64,
     pub avg_helpfulness: f64,
+    pub tag_stats: HashMap<String, TagStats>,
+    /// Set by the framework when recent feedback is mostly negative.
+    pub quality_warning: bool,
+    /// Calls per tool since startup; tools never called are absent. Set by
+    /// the framework.
+    pub tool_invocations: HashMap<String, u64>,
+    /// Mean milliseconds per call, keyed like `tool_invocations`.
+    pub avg_tool_latency: HashMap<String, f64>,
+    /// Share of the last `SUCCESS_RATE_WINDOW` generated trajectories that
+    /// succeeded. Set by the framework.
+    pub recent_success_rate: f64,
+    pub low_success_alarm: bool,
+}
+
+impl ContextStats {
+    /// Tags ordered by average helpfulness, best first.
+    pub fn tags_by_helpfulness(&self) -> Vec<(&String, &TagStats)> {
+        let mut tags: Vec<_> = self.tag_stats.iter().collect();
+        tags.sort_by(|a, b| b.1.avg_helpfulness.partial_cmp(&a.1.avg_helpfulness).unwrap());
+        tags
+    }
+}
+
+/// Startup task run by `ACEFramework::initialize` once the backends are up
+/// (or offline mode is entered), e.g. loading seed bullets.
+#[async_trait]
+pub trait InitHook: Sync {
+    async fn on_init(&self, framework: &mut ACEFramework) -> Result<()>;
+}
+
+#[derive(Clone)]
+pub struct OfflineMode {
+    pub fallback_response: String,
+}
+
+impl Default for OfflineMode {
+    fn default() -> Self {
+        Self {
+            fallback_response: "LLM unavailable and no matching context found.".to_string(),
+        }
+    }
+}
+
+fn verification_warning_header() -> String {
+    format!("\n\n{} Possible contradiction with learned context:", icon(IconKey::Warning))
+}
+
+fn format_verification_warning(report: &VerificationReport) -> Option<String> {
+    if report.contradictions.is_empty() {
+        return None;
+    }
+    let mut warning = verification_warning_header();
+    for c in &report.contradictions {
+        let preview: String = c.bullet_content.chars().take(80).collect();
+        warning.push_str(&format!(
+            "\n  - \"{}\" vs [{}] {}",
+            c.claim,
+            &c.bullet_id[..8.min(c.bullet_id.len())],
+            preview
+        ));
+    }
+    Some(warning)
+}
+
+/// Components an `ACEFramework` is assembled from. `new` wires the default
+/// Ollama/in-memory/DuckDuckGo stack; the `with_*` methods replace parts.
+pub struct ComponentContainer {
+    pub config: OllamaConfig,
+    pub llm: Box<dyn LlmBackend>,
+    /// Separate backend for reflection; `None` shares `llm`.
+    pub reflector_llm: Option<Box<dyn LlmBackend>>,
+    pub context_store: Box<dyn ContextStore>,
+    pub search_backend: Box<dyn SearchBackend>,
+}
+
+impl ComponentContainer {
+    pub fn new(config: OllamaConfig) -> Self {
+        Self {
+            llm: Box::new(OllamaClient::new(config.clone())),
+            reflector_llm: None,
+            context_store: Box::new(InMemoryContextStore::default()),
+            search_backend: Box::new(DuckDuckGoBackend::default()),
+            config,
+        }
+    }
+
+    pub fn with_llm(mut self, llm: Box<dyn LlmBackend>) -> Self {
+        self.llm = llm;
+        self
+    }
+
+    pub fn with_reflector_llm(mut self, llm: Box<dyn LlmBackend>) -> Self {
+        self.reflector_llm = Some(llm);
+        self
+    }
+
+    pub fn with_context_store(mut self, context_store: Box<dyn ContextStore>) -> Self {
+        self.context_store = context_store;
+        self
+    }
+
+    pub fn with_search_backend(mut self, search_backend: Box<dyn SearchBackend>) -> Self {
+        self.search_backend = search_backend;
+        self
+    }
 }
 
 #[allow(dead_code)]
 pub struct ACEFramework {
+    pub config: OllamaConfig,
     pub generator: ACEGenerator,
     pub reflector: ACEReflector,
     pub curator: ACECurator,
     pub thinking_tool: ThinkingTool,
+    pub search_backend: Arc<dyn SearchBackend>,
     pub web_search_enabled: bool,
+    pub offline_mode: Option<OfflineMode>,
+    pub post_processors: PostProcessorChain,
+    pub verification: VerificationConfig,
+    pub quality_check_enabled: bool,
+    pub insight_dedup: InsightDeduplicator,
+    pub trajectories: TrajectoryStore,
+    pub recordings: Vec<Recording>,
+    pub search_analytics: SearchAnalytics,
+    pub quality: QualityMonitor,
+    pub success_rate_window: SuccessRateWindow,
+    /// Bullets sent in the prompt of the latest query (quoted, when offline);
+    /// `/helpful` and `/unhelpful` rate these.
+    pub last_used_bullets: Vec<String>,
+    /// Query whose prompt carried `last_used_bullets`; `None` after an
+    /// offline answer, which had no prompt.
+    last_prompt_query: Option<String>,
+    /// Shared with background research tasks, which record when they finish.
+    tool_usage: Arc<Mutex<ToolUsage>>,
+    read_only: bool,
+    dry_run: bool,
+    state: LifecycleState,
+    backend_swaps: Option<tokio::sync::mpsc::Receiver<BackendSwap>>,
+    sync_changes: Option<(String, tokio::sync::mpsc::Receiver<SyncManifest>)>,
+    /// Background work started by `spawn_learn_from_interaction`.
+    pub pending_learns: Vec<tokio::task::JoinHandle<()>>,
+    compaction_results: (
+        tokio::sync::mpsc::UnboundedSender<Vec<CompactionSummary>>,
+        tokio::sync::mpsc::UnboundedReceiver<Vec<CompactionSummary>>,
+    ),
+    init_hooks: Vec<Box<dyn InitHook + Send>>,
+    pub rate_limiter: Option<RateLimiter>,
 }
 
 impl ACEFramework {
     pub fn new(config: OllamaConfig) -> Self {
-        let client1 = OllamaClient::new(config.clone());
-        let client2 = OllamaClient::new(config);
+        Self::from_container(ComponentContainer::new(config))
+    }
+
+    pub fn from_container(container: ComponentContainer) -> Self {
+        let llm: Arc<dyn LlmBackend> = Arc::from(container.llm);
+        let reflector_llm: Arc<dyn LlmBackend> = match container.reflector_llm {
+            Some(reflector_llm) => Arc::from(reflector_llm),
+            None => llm.clone(),
+        };
 
         Self {
-            generator: ACEGenerator::new(client1),
-            reflector: ACEReflector::new(client2),
-            curator: ACECurator::new(),
+            config: container.config,
+            generator: ACEGenerator::new(llm),
+            reflector: ACEReflector::new(reflector_llm),
+            curator: ACECurator::with_store(container.context_store),
             thinking_tool: ThinkingTool,
+            search_backend: Arc::from(container.search_backend),
             web_search_enabled: false,
+            offline_mode: None,
+            post_processors: PostProcessorChain::new(),
+            verification: VerificationConfig::default(),
+            quality_check_enabled: false,
+            insight_dedup: InsightDeduplicator::default(),
+            trajectories: TrajectoryStore::default(),
+            recordings: Vec::new(),
+            search_analytics: SearchAnalytics::default(),
+            quality: QualityMonitor::default(),
+            success_rate_window: SuccessRateWindow::default(),
+            last_used_bullets: Vec::new(),
+            last_prompt_query: None,
+            tool_usage: Arc::new(Mutex::new(ToolUsage::default())),
+            read_only: false,
+            dry_run: false,
+            state: LifecycleState::Created,
+            backend_swaps: None,
+            sync_changes: None,
+            pending_learns: Vec::new(),
+            compaction_results: tokio::sync::mpsc::unbounded_channel(),
+            init_hooks: Vec::new(),
+            rate_limiter: None,
+        }
+    }
+
+    pub fn with_reflector_strategy(&mut self, strategy: Box<dyn InsightExtractor>) -> &mut Self {
+        self.reflector.extractor = strategy;
+        self
+    }
+
+    /// Distinct LLM backends by role; the reflector is listed only when it
+    /// doesn't share the generator's client.
+    fn backends(&self) -> Vec<(&'static str, Arc<dyn LlmBackend>)> {
+        let mut backends = vec![("generator", self.generator.client.clone())];
+        if !Arc::ptr_eq(&self.generator.client, &self.reflector.client) {
+            backends.push(("reflector", self.reflector.client.clone()));
+        }
+        backends
+    }
+
+    pub fn register_init_hook(&mut self, hook: Box<dyn InitHook + Send>) {
+        self.init_hooks.push(hook);
+    }
+
+    pub fn state(&self) -> &LifecycleState {
+        &self.state
+    }
+
+    /// Serves the learned context as a knowledge base: queries to the LLM
+    /// and learning are refused, search, stats and `/qa` keep working.
+    pub fn in_read_only_mode(&mut self) {
+        self.read_only = true;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The curator, for commands that edit the context directly. Refused in
+    /// read-only mode.
+    pub fn curator_mut(&mut self) -> Result<&mut ACECurator> {
+        self.ensure_writable()?;
+        Ok(&mut self.curator)
+    }
+
+    /// Previews commands without side effects: web searches return nothing
+    /// and the context is never flushed, though learning still updates it in
+    /// memory. Canned LLM answers come from building the framework with
+    /// `OllamaConfig::dry_run`; setting it here too covers backends the
+    /// config watcher creates later.
+    pub fn in_dry_run_mode(&mut self) {
+        self.dry_run = true;
+        self.config.dry_run = true;
+        self.search_backend = Arc::new(DryRunSearchBackend);
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            Err("ACE is in read-only mode".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn ensure_serving(&self) -> Result<()> {
+        if self.state.is_serving() {
+            Ok(())
+        } else {
+            Err(format!("ACE is not ready (state: {:?})", self.state))
         }
     }
 
-    pub async fn initialize(&self) -> Result<bool> {
-        match self.generator.client.initialize().await {
-            Ok(_) => {
-                log_success("ACE Framework initialized");
-                Ok(true)
+    /// Initializes the backends, then runs the registered init hooks in
+    /// order. The first hook error is returned.
+    ///
+    /// Moves `Created → Initializing → Ready`, or `→ Degraded` when a backend
+    /// or hook fails; if the backends cannot be set up at all the framework
+    /// goes back to `Created`.
+    pub async fn initialize(&mut self, init: &InitConfig) -> Result<InitResult> {
+        self.state = LifecycleState::Initializing;
+        let result = match self.initialize_backends(init).await {
+            Ok(result) => result,
+            Err(e) => {
+                self.state = LifecycleState::Created;
+                return Err(e);
+            }
+        };
+        self.state = if result.backends_unavailable.is_empty() {
+            LifecycleState::Ready
+        } else {
+            LifecycleState::Degraded(result.backends_unavailable.clone())
+        };
+        #[cfg(feature = "embedding")]
+        if self.dry_run {
+            log_info("Dry run: skipping the embedding model download");
+        } else if let Err(e) = crate::embedding::EmbeddingModelCache::default()
+            .ensure_model(crate::embedding::DEFAULT_EMBEDDING_MODEL)
+            .await
+        {
+            log_warning(&format!("Embedding model unavailable: {}", e));
+        }
+        let hooks = std::mem::take(&mut self.init_hooks);
+        let mut outcome = Ok(());
+        for hook in &hooks {
+            outcome = hook.on_init(self).await;
+            if outcome.is_err() {
+                break;
             }
+        }
+        self.init_hooks = hooks;
+        if let Err(e) = &outcome {
+            let mut unavailable = result.backends_unavailable.clone();
+            unavailable.push(format!("init hook: {}", e));
+            self.state = LifecycleState::Degraded(unavailable);
+        }
+        outcome.map(|_| result)
+    }
+
+    /// Finishes background learning, flushes the context store (unless in
+    /// dry-run mode) and stops serving queries.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        self.state = LifecycleState::ShuttingDown;
+        self.finish_pending_learns().await;
+        let flushed = if self.dry_run { Ok(()) } else { self.curator.flush() };
+        self.state = LifecycleState::Shutdown;
+        flushed
+    }
+
+    /// Pings every backend concurrently. An unavailable reflector falls back
+    /// to the generator; an unavailable generator switches to offline mode.
+    async fn initialize_backends(&mut self, init: &InitConfig) -> Result<InitResult> {
+        let backends = self.backends();
+        let pings = backends
+            .iter()
+            .map(|(_, client)| initialize_with_retry(client.as_ref(), init));
+        let outcomes = futures::future::join_all(pings).await;
+
+        let mut result = InitResult::default();
+        for ((name, _), outcome) in backends.iter().zip(outcomes) {
+            match outcome {
+                Ok(_) => result.backends_ready.push(name.to_string()),
+                Err(e) => {
+                    log_warning(&format!("{} backend unavailable: {}", name, e));
+                    result.backends_unavailable.push(name.to_string());
+                }
+            }
+        }
+
+        if !result.is_ready("generator") {
+            log_error("Initialization failed: generator backend unavailable");
+            log_info("Entering offline mode: answers come from learned context only");
+            self.offline_mode = Some(OfflineMode::default());
+            return Ok(result);
+        }
+        if result.backends_unavailable.iter().any(|b| b == "reflector") {
+            log_info("Reflecting with the generator backend instead");
+            self.reflector.client = self.generator.client.clone();
+        }
+
+        self.offline_mode = None;
+        self.ensure_model(init.auto_pull).await?;
+        self.detect_context_window().await;
+        log_success("ACE Framework initialized");
+        Ok(result)
+    }
+
+    /// Points the shared generator/reflector client at `model`, keeping the
+    /// learned context. Rolls back if the backend can't serve the new model.
+    pub async fn switch_model(&mut self, model: &str) -> Result<()> {
+        let previous = self.config.model.clone();
+        let client = self.generator.client.clone();
+        client.set_model(model);
+
+        let verified = match client.initialize().await {
+            Ok(_) => client.model_info().await,
+            Err(e) => Err(e),
+        };
+        let info = match verified {
+            Ok(info) => info,
             Err(e) => {
-                log_error(&format!("Initialization failed: {}", e));
-                Err(e)
+                client.set_model(&previous);
+                return Err(format!("Cannot switch to {}: {}", model, e));
+            }
+        };
+
+        self.config.model = model.to_string();
+        self.offline_mode = None;
+        log_success(&format!(
+            "Switched from {} to {}. Context contains {} bullets.",
+            previous,
+            model,
+            self.curator.get_context().bullets.len()
+        ));
+        println!(
+            "  Architecture: {}\n  Parameters: {}\n  Context length: {}",
+            info.architecture, info.parameter_count, info.context_length
+        );
+        self.detect_context_window().await;
+        Ok(())
+    }
+
+    /// Starts watching `path` for a changed `model`; the new backend is
+    /// prepared in the background and picked up by `apply_backend_swaps`.
+    pub fn watch_config(&mut self, path: &std::path::Path) {
+        self.backend_swaps = Some(watch_config_model(path.to_path_buf(), self.config.clone()));
+    }
+
+    /// Swaps in the newest backend prepared by the config watcher, if any.
+    /// Requests already running keep the `Arc` of the old backend.
+    pub fn apply_backend_swaps(&mut self) {
+        let Some(swaps) = self.backend_swaps.as_mut() else {
+            return;
+        };
+        let mut latest = None;
+        while let Ok(swap) = swaps.try_recv() {
+            latest = Some(swap);
+        }
+        let Some(swap) = latest else {
+            return;
+        };
+
+        if Arc::ptr_eq(&self.generator.client, &self.reflector.client) {
+            self.reflector.client = swap.backend.clone();
+        }
+        self.generator.client = swap.backend;
+        let previous = std::mem::replace(&mut self.config.model, swap.model);
+        self.offline_mode = None;
+        if let LifecycleState::Degraded(parts) = &mut self.state {
+            parts.retain(|part| part != "generator");
+            if parts.is_empty() {
+                self.state = LifecycleState::Ready;
             }
         }
+        log_success(&format!("Backend switched from {} to {}", previous, self.config.model));
+    }
+
+    async fn ensure_model(&self, auto_pull: bool) -> Result<()> {
+        let model = &self.config.model;
+        match self.generator.client.model_info().await {
+            Err(e) if e.starts_with(MODEL_NOT_FOUND) && auto_pull => self.pull_model(model).await,
+            Err(e) if e.starts_with(MODEL_NOT_FOUND) => Err(format!(
+                "Model not found. Run `ollama pull {}` or set auto_pull = true in ace.toml.",
+                model
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    async fn pull_model(&self, model: &str) -> Result<()> {
+        log_info(&format!("Pulling {}...", model));
+        let mut progress = self.generator.client.pull_model(model).await?;
+
+        let bar = ProgressBar::new(100);
+        bar.set_style(
+            ProgressStyle::with_template("{bar:40.cyan/blue} {pos:>3}% {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+
+        let mut last_status = String::new();
+        while let Some(update) = progress.next().await {
+            if let Some(error) = update.status.strip_prefix("error: ") {
+                bar.abandon();
+                return Err(format!("Pull failed: {}", error));
+            }
+            bar.set_position(update.percent.round() as u64);
+            bar.set_message(update.status.clone());
+            last_status = update.status;
+        }
+        bar.finish_and_clear();
+
+        if last_status == "success" {
+            log_success(&format!("Pulled {}", model));
+            Ok(())
+        } else {
+            Err(format!("Pull of {} ended unexpectedly ({})", model, last_status))
+        }
+    }
+
+    async fn detect_context_window(&mut self) {
+        let info = match self.generator.client.model_info().await {
+            Ok(info) => info,
+            Err(e) => {
+                log_debug(&format!("Model info unavailable: {}", e));
+                return;
+            }
+        };
+
+        if info.context_length > self.config.context_window {
+            log_info(&format!(
+                "Context window raised from {} to {} ({} {})",
+                self.config.context_window, info.context_length, info.architecture, info.parameter_count
+            ));
+            self.config.context_window = info.context_length;
+            self.generator.client.set_context_window(info.context_length);
+        } else if info.context_length < self.config.context_window {
+            log_error(&format!(
+                "Configured context_window {} exceeds the model limit of {}",
+                self.config.context_window, info.context_length
+            ));
+        }
+    }
+
+    /// Charges `estimated_tokens` against the rate limiter, if one is set.
+    pub fn check_rate_limit(&mut self, estimated_tokens: usize) -> Result<()> {
+        let Some(limiter) = &mut self.rate_limiter else {
+            return Ok(());
+        };
+        limiter.try_acquire(estimated_tokens).map_err(|retry_after| {
+            format!("Rate limit reached. Retry in {}s.", retry_after.as_secs_f64().ceil() as u64)
+        })
+    }
+
+    /// Hands over the interactions recorded since the last call.
+    pub fn record_session(&mut self) -> Vec<Recording> {
+        std::mem::take(&mut self.recordings)
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline_mode.is_some()
+    }
+
+    /// Topic of the last `TOPIC_EXCHANGES` exchanges.
+    pub fn conversation_topic(&self) -> Vec<String> {
+        detect_conversation_topic(&recent_conversation(self.curator.get_context(), TOPIC_EXCHANGES))
+    }
+
+    /// `get_relevant_bullets`, scoped to the conversation topic when the
+    /// curator's `topic_scoped` is set.
+    pub fn relevant_bullets(&self, query: &str, max_bullets: usize) -> Vec<ContextBullet> {
+        let context = self.curator.get_context();
+        if self.curator.config.topic_scoped {
+            get_topic_scoped_bullets(context, query, max_bullets, &self.conversation_topic())
+        } else {
+            get_relevant_bullets(context, query, max_bullets)
+        }
+    }
+
+    /// `relevant_bullets`, counting the retrieval on each bullet returned.
+    pub fn retrieve_bullets(&mut self, query: &str, max_bullets: usize) -> Vec<ContextBullet> {
+        let bullets = self.relevant_bullets(query, max_bullets);
+        let ids: Vec<String> = bullets.iter().map(|b| b.id.clone()).collect();
+        self.curator.record_access(&ids);
+        bullets
+    }
+
+    fn offline_response(bullets: &[ContextBullet], offline: &OfflineMode) -> String {
+        if bullets.is_empty() {
+            return offline.fallback_response.clone();
+        }
+
+        let mut output = format!("{} Offline mode - answering from learned context:\n", icon(IconKey::Offline));
+        for (i, b) in bullets.iter().enumerate() {
+            output.push_str(&format!("{}. {}\n", i + 1, b.content));
+        }
+        output
+    }
+
+    /// The prompt `process_query_stream` sends for `query`, with the context
+    /// bullets it carries: `relevant_bullets`, so topic-scoped when the
+    /// curator's `topic_scoped` is set.
+    pub fn query_prompt(&self, query: &str) -> (String, Vec<ContextBullet>) {
+        let context = self.curator.get_context();
+        let bullets = query_context_bullets(context, query, self.relevant_bullets(query, QUERY_CONTEXT_BULLETS));
+        let prompt = build_query_prompt(
+            context,
+            query,
+            &bullets,
+            self.config.force_response_language.as_deref(),
+            self.curator.config.rich_context,
+        );
+        (prompt, bullets)
     }
 
     pub async fn process_query_stream(
         &mut self,
         query: &str,
     ) -> Result<impl futures::Stream<Item = Result<String>>> {
-        let context = self.curator.get_context();
-        
-        // Get recent conversation bullets
-        let mut conv_bullets: Vec<_> = context.bullets.values()
-            .filter(|b| b.tags.contains(&"conversation".to_string()))
+        self.ensure_serving()?;
+        self.ensure_writable()?;
+        self.apply_backend_swaps();
+        if let Some(offline) = self.offline_mode.clone() {
+            let bullets = self.retrieve_bullets(query, 3);
+            self.last_used_bullets = bullets.iter().map(|b| b.id.clone()).collect();
+            self.last_prompt_query = None;
+            let response = Self::offline_response(&bullets, &offline);
+            return Ok(futures::stream::iter(vec![Ok(response)]).left_stream());
+        }
+
+        let (prompt, bullets) = self.query_prompt(query);
+        let used: Vec<String> = bullets.iter().map(|b| b.id.clone()).collect();
+        self.curator.record_access(&used);
+        self.search_analytics.mark_used(&used);
+        self.last_used_bullets = used;
+        self.last_prompt_query = Some(query.to_string());
+
+        let options = GenerationOptions {
+            temperature: self
+                .config
+                .adaptive_temperature
+                .map(|adaptive| adaptive.temperature_for(classify_query(query))),
+            ..Default::default()
+        };
+        let stream = self.generator.client.generate_stream_with_options(&prompt, &options).await?;
+        if !self.verification.enabled {
+            return Ok(stream.right_stream());
+        }
+
+        // Verify once the full answer has streamed against the bullets it was
+        // given, then append any warning
+        let collected = Arc::new(Mutex::new(String::new()));
+        let sink = collected.clone();
+        let body = stream.map(move |chunk| {
+            if let Ok(text) = &chunk {
+                sink.lock().unwrap().push_str(text);
+            }
+            chunk
+        });
+        let warning = futures::stream::once(async move {
+            let response = collected.lock().unwrap().clone();
+            format_verification_warning(&verify_response(&response, &bullets)).map(Ok)
+        })
+        .filter_map(|warning| async move { warning });
+
+        Ok(body.chain(warning).boxed().right_stream())
+    }
+
+    /// Runs the query like `process_query_stream` and reports the prompt sent
+    /// and the context bullets it carried (those quoted, when offline).
+    pub async fn process_query_with_explanation(&mut self, query: &str) -> Result<ExplainedResponse> {
+        let query_words = tokenize(query);
+        let (prompt_used, bullets) = if self.is_offline() {
+            (String::new(), self.relevant_bullets(query, 3))
+        } else {
+            self.query_prompt(query)
+        };
+        let contributing_bullets = bullets
+            .into_iter()
+            .map(|b| {
+                let score = score_bullet(&b, &query_words);
+                (b, score)
+            })
             .collect();
-        conv_bullets.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-        let recent_conv: Vec<_> = conv_bullets.into_iter().take(1).cloned().collect();
-        
-        let is_continue = query.trim().to_lowercase() == "continue" || 
-                         query.trim().to_lowercase() == "tiếp tục";
-
-        let prompt = if is_continue && !recent_conv.is_empty() {
-            let last_conv = &recent_conv[0].content;
-            format!(
-                "{}\n\nContinue from where you stopped. Do not repeat, just continue:",
-                last_conv
-            )
-        } else if !recent_conv.is_empty() {
-            let context_text = build_context_prompt(&recent_conv);
-            format!(
-                "Previous conversation:\n{}\n\nNew query: {}\n\nAnswer:",
-                context_text, query
-            )
+
+        let mut stream = Box::pin(self.process_query_stream(query).await?);
+        let mut response = String::new();
+        while let Some(chunk) = stream.next().await {
+            response.push_str(&chunk?);
+        }
+
+        Ok(ExplainedResponse {
+            response,
+            contributing_bullets,
+            prompt_used,
+        })
+    }
+
+    /// Asks `question` about the image at `path`; the model must support vision.
+    pub async fn ask_about_image(&self, path: &std::path::Path, question: &str) -> Result<String> {
+        if self.is_offline() {
+            return Err("LLM unavailable, offline mode active.".to_string());
+        }
+        let started = std::time::Instant::now();
+        let image = FileReadTool.read_image(path);
+        self.record_tool_use("FileReadTool", started);
+        let image = image?;
+        let prompt = MultimodalPrompt {
+            text: question.to_string(),
+            images: vec![image],
+        };
+        self.generator.client.generate_multimodal(&prompt).await
+    }
+
+    /// A grounded answer taken verbatim from the top bullets, without calling
+    /// the LLM. `None` when no bullet answers the query directly.
+    pub fn answer_from_context(&self, query: &str) -> Option<String> {
+        let bullets = self.relevant_bullets(query, 5);
+        find_direct_answer(query, &bullets)
+    }
+
+    /// Token budget of the prompt `process_query_stream` would send for `query`.
+    pub fn get_context_window_view(&self, query: &str) -> ContextWindowView {
+        let (_, bullets) = self.query_prompt(query);
+        context_window_view(
+            self.curator.get_context(),
+            query,
+            &bullets,
+            self.config.force_response_language.as_deref(),
+            self.config.context_window.max(0) as usize,
+            self.curator.config.rich_context,
+        )
+    }
+
+    /// Has the reflector model rate `response`, records the interaction as a
+    /// trajectory carrying the score, and pins the bullets that were in the
+    /// prompt of responses averaging `QUALITY_PIN_THRESHOLD` or more. Only
+    /// runs when `quality_check_enabled` is set.
+    pub async fn process_quality_check(&mut self, query: &str, response: &str) -> Result<QualityAssessment> {
+        if !self.quality_check_enabled {
+            return Err("Quality check is disabled".to_string());
+        }
+        if self.is_offline() {
+            return Err("LLM unavailable, offline mode active.".to_string());
+        }
+        let assessment = QualityAssessor::new(self.reflector.client.clone())
+            .assess(query, response)
+            .await?;
+        let score = assessment.average();
+        // Only the bullets in this query's prompt stand behind the response
+        let used = if self.last_prompt_query.as_deref() == Some(query) {
+            self.last_used_bullets.clone()
         } else {
-            query.to_string()
+            Vec::new()
         };
+        self.trajectories.push(Trajectory {
+            id: uuid::Uuid::new_v4().to_string(),
+            query: query.to_string(),
+            steps: Vec::new(),
+            outcome: response.to_string(),
+            success: true,
+            used_bullets: used.clone(),
+            feedback: None,
+            quality_score: Some(score),
+        });
+
+        if score >= QUALITY_PIN_THRESHOLD && !self.read_only {
+            let pinned = used.iter().filter(|id| self.curator.pin_bullet(id).is_ok()).count();
+            if pinned > 0 {
+                log_info(&format!("Pinned {} bullets behind a high-quality answer", pinned));
+            }
+        }
+        Ok(assessment)
+    }
+
+    pub fn verify_response(&self, query: &str, response: &str) -> VerificationReport {
+        let bullets = self.relevant_bullets(query, 5);
+        verify_response(response, &bullets)
+    }
+
+    pub async fn learn_from_interaction(&mut self, query: &str, response: &str) -> Result<()> {
+        self.learn(query, response, false)?;
+        self.run_pending_compaction().await;
+        Ok(())
+    }
+
+    /// Keeps an answer that was interrupted mid-stream; the bullet is marked
+    /// `partial: true` in its metadata so it can be found and refined later.
+    pub async fn learn_from_partial_interaction(&mut self, query: &str, response: &str) -> Result<()> {
+        self.learn(query, response, true)?;
+        self.run_pending_compaction().await;
+        Ok(())
+    }
+
+    /// `learn_from_interaction` without waiting on the LLM: the bullet is
+    /// stored right away (an in-memory update), while a compaction it
+    /// triggers is summarized in a background task tracked in
+    /// `pending_learns`. Its result lands in the context on
+    /// `finish_pending_learns`.
+    pub fn spawn_learn_from_interaction(&mut self, query: String, response: String) {
+        if let Err(e) = self.learn(&query, &response, false) {
+            log_error(&e);
+            return;
+        }
+        if !self.curator.compaction_pending() || self.is_offline() {
+            return;
+        }
+        let groups = self.curator.take_compaction_groups();
+        let client = self.generator.client.clone();
+        let results = self.compaction_results.0.clone();
+        self.pending_learns.push(tokio::spawn(async move {
+            match summarize_groups(client.as_ref(), &groups).await {
+                Ok(summaries) => {
+                    let _ = results.send(summaries);
+                }
+                Err(e) => log_warning(&format!("Context compaction failed: {}", e)),
+            }
+        }));
+    }
 
-        let stream = self.generator.client.generate_stream(&prompt).await?;
-        Ok(stream)
+    /// Waits for background learning and applies what it produced.
+    pub async fn finish_pending_learns(&mut self) {
+        for handle in std::mem::take(&mut self.pending_learns) {
+            let _ = handle.await;
+        }
+        while let Ok(summaries) = self.compaction_results.1.try_recv() {
+            self.curator.apply_compaction(summaries);
+        }
     }
 
-    pub async fn learn_from_interaction(&mut self, query: &str, response: &str) {
+    /// Runs the compaction the curator asked for, if any. Failures leave the
+    /// context as it was; eviction still bounds its size.
+    async fn run_pending_compaction(&mut self) {
+        if !self.curator.compaction_pending() || self.is_offline() {
+            return;
+        }
+        let client = self.generator.client.clone();
+        if let Err(e) = self.curator.compact_via_summarization(client.as_ref()).await {
+            log_warning(&format!("Context compaction failed: {}", e));
+        }
+    }
+
+    fn learn(&mut self, query: &str, response: &str, partial: bool) -> Result<()> {
+        self.ensure_writable()?;
+        // Offline answers are echoes of existing bullets; don't learn them back
+        if self.is_offline() {
+            return Ok(());
+        }
+
         // Save full conversation as context
+        let response = response.split(&verification_warning_header()).next().unwrap_or(response);
+        self.recordings.push(Recording {
+            query: query.to_string(),
+            response: response.to_string(),
+        });
+        let response = self.post_processors.process(response);
         let conv_text = format!("Q: {}\nA: {}", query, response);
-        let bullet = create_bullet(conv_text, vec!["conversation".to_string()]);
+        let mut bullet = create_bullet(conv_text, vec!["conversation".to_string()]);
+        if partial {
+            bullet.metadata.insert("partial".to_string(), serde_json::Value::Bool(true));
+        }
         let delta = DeltaUpdate {
             bullets: vec![bullet],
             timestamp: chrono::Utc::now(),
         };
         self.curator.apply_delta(&delta);
+        Ok(())
     }
     
+    /// Seeds the context from a `.txt`, `.md` or `.rs` file: each chunk of
+    /// about `chunk_size` characters is reflected on as if it were the
+    /// outcome of a task, and the resulting insights are stored as bullets.
+    /// Chunks the reflector fails on are skipped.
+    pub async fn ingest_document(&mut self, path: &std::path::Path, chunk_size: usize) -> Result<IngestReport> {
+        self.ensure_writable()?;
+        let started = std::time::Instant::now();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !matches!(extension, "txt" | "md" | "rs") {
+            return Err(format!("Unsupported file type '{}': expected .txt, .md or .rs", extension));
+        }
+        if self.is_offline() {
+            return Err("LLM unavailable, offline mode active.".to_string());
+        }
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("document");
+
+        let mut report = IngestReport::default();
+        for chunk in chunk_text(&text, chunk_size.max(1)) {
+            let trajectory = Trajectory {
+                id: uuid::Uuid::new_v4().to_string(),
+                query: format!("Learn from the document {}", name),
+                steps: Vec::new(),
+                outcome: chunk,
+                success: true,
+                used_bullets: Vec::new(),
+                feedback: None,
+                quality_score: None,
+            };
+            let insights = match self.reflect(&trajectory).await {
+                Ok(insights) => insights,
+                Err(e) => {
+                    log_warning(&format!("Skipping chunk {}: {}", report.chunks_processed + 1, e));
+                    continue;
+                }
+            };
+            report.chunks_processed += 1;
+
+            let delta = insights_to_delta(insights);
+            self.curator.apply_delta(&delta);
+            let context = self.curator.get_context();
+            report.bullets_created += delta.bullets.iter().filter(|b| context.bullets.contains_key(&b.id)).count();
+        }
+        self.run_pending_compaction().await;
+        report.elapsed_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    pub async fn generate_trajectory(&mut self, query: &str) -> Result<Trajectory> {
+        let bullets = self.retrieve_bullets(query, 10);
+        let trajectory = self.generator.generate_trajectory(query, &bullets).await?;
+        self.record_trajectory_outcome(trajectory.success);
+        self.trajectories.push(trajectory.clone());
+        Ok(trajectory)
+    }
+
+    fn record_trajectory_outcome(&mut self, success: bool) {
+        if self.success_rate_window.update(success) {
+            log_error(&format!(
+                "High trajectory failure rate: {:.0}% in last {} queries",
+                (1.0 - self.success_rate_window.rate()) * 100.0,
+                SUCCESS_RATE_WINDOW
+            ));
+        }
+    }
+
+    pub fn get_recent_trajectories(&self, n: usize) -> Vec<&Trajectory> {
+        self.trajectories.recent(n)
+    }
+
+    /// Re-runs an unfinished trajectory with its earlier steps as a starting
+    /// point. The result replaces the stored entry under the same id.
+    pub async fn resume_trajectory(&mut self, id: &str) -> Result<Trajectory> {
+        let previous = self
+            .trajectories
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("No trajectory with id {}", id))?;
+
+        let steps: Vec<String> = previous.steps.iter().map(|s| format!("- {}", s.description)).collect();
+        let query = format!(
+            "{}\n\nSteps completed so far:\n{}\nContinue from there.",
+            previous.query,
+            steps.join("\n")
+        );
+
+        let bullets = self.retrieve_bullets(&previous.query, 10);
+        let mut resumed = self.generator.generate_trajectory(&query, &bullets).await?;
+        self.record_trajectory_outcome(resumed.success);
+        resumed.id = previous.id;
+        resumed.query = previous.query;
+        self.trajectories.replace(id, resumed.clone());
+        Ok(resumed)
+    }
+
+    /// Reflects on a trajectory, dropping insights already seen this session.
+    pub async fn reflect(&mut self, trajectory: &Trajectory) -> Result<Vec<Insight>> {
+        self.reflector.reflect_deduped(trajectory, &mut self.insight_dedup).await
+    }
+
     pub async fn think(&self, query: &str) -> Result<String> {
-        self.thinking_tool.think(query, &self.generator.client).await
+        self.ensure_serving()?;
+        if self.is_offline() {
+            return Err("LLM unavailable, offline mode active.".to_string());
+        }
+        let started = std::time::Instant::now();
+        let thought = self.thinking_tool.think(query, self.generator.client.as_ref()).await;
+        self.record_tool_use("ThinkingTool", started);
+        Ok(self.post_processors.process(&thought?))
     }
 
-    pub async fn search_query(&self, query: &str) -> String {
+    pub async fn search_query(&mut self, query: &str) -> SearchPage {
+        self.search_query_faceted(query, None, None).await
+    }
+
+    /// `tag` and `since` limit results to learned bullets with that tag or
+    /// created since then (web results have neither and are skipped).
+    pub async fn search_query_faceted(
+        &mut self,
+        query: &str,
+        tag: Option<&str>,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> SearchPage {
         let context = self.curator.get_context();
-        let search_tool = SearchTool::new(self.web_search_enabled);
-        let results = search_tool.search(query, &context.bullets).await;
-        
-        if results.is_empty() {
-            return "No results found.".to_string();
-        }
-        
-        let mut output = String::new();
-        for (i, r) in results.iter().enumerate() {
-            let source = if r.source == "web" { "🌐" } else { "📚" };
-            output.push_str(&format!(
-                "{}. {} {}...\n",
-                i + 1,
-                source,
-                &r.content.chars().take(100).collect::<String>()
-            ));
-            if let Some(url) = &r.url {
-                output.push_str(&format!("   🔗 {}\n", url));
+        let search_tool = SearchTool::with_backend(self.web_search_enabled, self.search_backend.clone())
+            .with_stop_words(self.curator.stop_words().clone());
+        let recent;
+        let bullets = match since {
+            Some(since) => {
+                recent = bullets_created_since(&context.bullets, since);
+                &recent
             }
-        }
-        output
+            None => &context.bullets,
+        };
+        let started = std::time::Instant::now();
+        let page = match (tag, since) {
+            (None, None) => search_tool.search_with_fallback(query, bullets).await,
+            _ => search_tool.search_context_page(query, bullets, tag),
+        };
+        self.record_tool_use("SearchTool", started);
+        let shown = page.results.iter().filter_map(|r| r.bullet_id.clone()).collect();
+        self.search_analytics.record(query, shown);
+        page
     }
 
-    pub async fn research(&self, topic: &str) -> Result<String> {
+    pub async fn research(&self, topic: &str) -> Result<ResearchReport> {
+        self.research_with_template(topic, ResearchTemplate::default()).await
+    }
+
+    pub async fn research_with_template(
+        &self,
+        topic: &str,
+        template: ResearchTemplate,
+    ) -> Result<ResearchReport> {
+        self.ensure_serving()?;
+        if self.is_offline() {
+            return Err("LLM unavailable, offline mode active.".to_string());
+        }
         let context = self.curator.get_context();
-        let research_tool = DeepResearchTool::new(self.web_search_enabled);
-        research_tool.research(topic, &self.generator.client, &context.bullets).await
+        let research_tool = DeepResearchTool::new(self.web_search_enabled)
+            .with_template(template)
+            .with_search_backend(self.search_backend.clone());
+        let started = std::time::Instant::now();
+        let report = research_tool.research(topic, self.generator.client.as_ref(), &context.bullets).await;
+        self.record_tool_use("DeepResearchTool", started);
+        report
+    }
+
+    /// `research_with_template`, streaming progress events as it runs.
+    pub fn research_with_progress(
+        &self,
+        topic: &str,
+        template: ResearchTemplate,
+    ) -> Result<tokio::sync::mpsc::Receiver<ResearchProgressEvent>> {
+        self.ensure_serving()?;
+        if self.is_offline() {
+            return Err("LLM unavailable, offline mode active.".to_string());
+        }
+        let research_tool = DeepResearchTool::new(self.web_search_enabled)
+            .with_template(template)
+            .with_search_backend(self.search_backend.clone());
+        let mut inner = research_tool.research_with_progress(
+            topic,
+            self.generator.client.clone(),
+            self.curator.get_context().bullets.clone(),
+        );
+
+        // Forward the events so the call is recorded once research finishes
+        let (events, receiver) = tokio::sync::mpsc::channel(32);
+        let tool_usage = self.tool_usage.clone();
+        let started = std::time::Instant::now();
+        tokio::spawn(async move {
+            while let Some(event) = inner.recv().await {
+                if events.send(event).await.is_err() {
+                    break;
+                }
+            }
+            if let Ok(mut usage) = tool_usage.lock() {
+                usage.record("DeepResearchTool", started.elapsed());
+            }
+        });
+        Ok(receiver)
+    }
+
+    fn record_tool_use(&self, tool: &str, started: std::time::Instant) {
+        if let Ok(mut usage) = self.tool_usage.lock() {
+            usage.record(tool, started.elapsed());
+        }
     }
     
     pub fn get_context_stats(&self) -> ContextStats {
-        self.curator.get_stats()
+        let usage = self.tool_usage.lock().map(|usage| usage.clone()).unwrap_or_default();
+        ContextStats {
+            quality_warning: self.quality.is_degrading(),
+            tool_invocations: usage.invocations(),
+            avg_tool_latency: usage.avg_latency_ms(),
+            recent_success_rate: self.success_rate_window.rate(),
+            low_success_alarm: self.success_rate_window.is_alarmed(),
+            ..self.curator.get_stats()
+        }
+    }
+
+    /// Rates the latest response: the bullets it was built from get the
+    /// feedback, and the quality monitor is updated. Returns how many bullets
+    /// were rated.
+    pub fn record_feedback(&mut self, helpful: bool) -> Result<usize> {
+        self.ensure_writable()?;
+        let rated = self.curator.record_feedback(&self.last_used_bullets, helpful);
+        if self.quality.update(helpful) {
+            log_warning("Context quality degrading: consider reviewing /bullets and /duplicates");
+        }
+        Ok(rated)
+    }
+
+    pub fn get_context_health(&self) -> ContextHealth {
+        self.curator.get_health()
+    }
+
+    /// Polls `remote_url` in the background; changes are pulled by
+    /// `apply_sync_changes`.
+    pub fn watch_sync_remote(&mut self, remote_url: &str, poller: SyncPoller, webhook: Option<String>) {
+        let changes = poller.spawn(remote_url.to_string(), webhook);
+        self.sync_changes = Some((remote_url.to_string(), changes));
+    }
+
+    /// Pulls from the watched remote if the poller saw its manifest change.
+    /// Returns `None` when there was nothing to pull.
+    pub async fn apply_sync_changes(&mut self) -> Option<Result<SyncReport>> {
+        let (remote, changes) = self.sync_changes.as_mut()?;
+        let mut changed = false;
+        while changes.try_recv().is_ok() {
+            changed = true;
+        }
+        if !changed || self.read_only {
+            return None;
+        }
+        let remote = remote.clone();
+        Some(self.pull_from_remote(&remote).await)
+    }
+
+    /// Fetches the remote manifest, downloads bullets that are missing or
+    /// newer there, and stores them locally.
+    pub async fn pull_from_remote(&mut self, remote_url: &str) -> Result<SyncReport> {
+        self.ensure_writable()?;
+        let remote = fetch_sync_manifest(remote_url).await?;
+        let plan = plan_sync(&compute_sync_manifest(self.curator.get_context()), &remote);
+        let bullets = fetch_sync_bullets(remote_url, &plan.fetch).await?;
+
+        let mut report = SyncReport {
+            conflicts: plan.conflicts,
+            ..SyncReport::default()
+        };
+        if bullets.is_empty() {
+            return Ok(report);
+        }
+        let mut context = self.curator.get_context().clone();
+        context.version = context.version.merge(&remote.clock);
+        for bullet in bullets {
+            if context.bullets.insert(bullet.id.clone(), bullet).is_some() {
+                report.updated += 1;
+            } else {
+                report.added += 1;
+            }
+        }
+        context.version.increment();
+        self.curator.set_context(context);
+        Ok(report)
+    }
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl ACEFramework {
+    /// A framework whose LLM replays `recordings` in order, for golden tests.
+    pub fn in_replay_mode(recordings: Vec<Recording>) -> Self {
+        let backend = crate::testing::ReplayMode::new(recordings).into_backend();
+        Self::from_container(ComponentContainer::new(OllamaConfig::default()).with_llm(Box::new(backend)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture_bullet, fixture_context, MockLlmBackend};
+
+    /// A serving framework over `context` whose LLM is `llm`.
+    fn framework(llm: MockLlmBackend, context: ContextState) -> ACEFramework {
+        let mut ace = ACEFramework::from_container(ComponentContainer::new(OllamaConfig::default()).with_llm(Box::new(llm)));
+        ace.curator.set_context(context);
+        ace.state = LifecycleState::Ready;
+        ace
+    }
+
+    #[test]
+    fn content_hash_addressing_stores_duplicates_once() {
+        let mut curator = ACECurator::new();
+        curator.config.addressing = BulletAddressing::ContentHash;
+        let delta = || DeltaUpdate {
+            bullets: vec![create_bullet("Run cargo fmt before committing".to_string(), vec!["rust".to_string()])],
+            timestamp: chrono::Utc::now(),
+        };
+
+        curator.apply_delta(&delta());
+        curator.apply_delta(&delta());
+
+        let bullets = &curator.get_context().bullets;
+        assert_eq!(bullets.len(), 1);
+        assert!(bullets.contains_key(&content_id(&delta().bullets[0].content)));
+    }
+
+    #[tokio::test]
+    async fn query_prompt_snapshot() {
+        let llm = MockLlmBackend::new().expect("Run cargo fmt.");
+        let calls = llm.calls.clone();
+        let mut ace = framework(
+            llm,
+            fixture_context(vec![
+                fixture_bullet("rustfmt-1", "Run cargo fmt before committing Rust code", &["rust"], 3, 0),
+                fixture_bullet("clippy-02", "Treat clippy warnings as errors in CI", &["rust", "ci"], 1, 1),
+                fixture_bullet("python-3", "Use black to format Python code", &["python"], 0, 2),
+                fixture_bullet(
+                    "conv-0004",
+                    "Q: How do I lint Rust code?\nA: Use cargo clippy.",
+                    &["conversation"],
+                    0,
+                    3,
+                ),
+            ]),
+        );
+
+        let stream = ace.process_query_stream("How should I format Rust code?").await.unwrap();
+        let response: Vec<String> = stream.map(|chunk| chunk.unwrap()).collect().await;
+
+        assert_eq!(response.concat(), "Run cargo fmt.");
+        let calls = calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 1);
+        insta::assert_snapshot!(calls[0]);
     }
 }
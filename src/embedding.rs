@@ -0,0 +1,161 @@
+// ACE Embedding - local cache of ONNX embedding models
+#![allow(dead_code)]
+use crate::functional_core::sha256_hex;
+use crate::imperative_shell::{log_info, log_success, log_warning};
+use crate::types::*;
+use futures::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "all-MiniLM-L6-v2";
+
+const HUGGINGFACE_URL: &str = "https://huggingface.co";
+const CHECKSUM_FILE: &str = "checksums.sha256";
+
+struct KnownModel {
+    name: &'static str,
+    repo: &'static str,
+    files: &'static [&'static str],
+}
+
+const KNOWN_MODELS: &[KnownModel] = &[KnownModel {
+    name: "all-MiniLM-L6-v2",
+    repo: "Qdrant/all-MiniLM-L6-v2-onnx",
+    files: &[
+        "model.onnx",
+        "tokenizer.json",
+        "config.json",
+        "special_tokens_map.json",
+        "tokenizer_config.json",
+    ],
+}];
+
+/// Downloaded models live in `<model_dir>/<model name>/`, next to a
+/// `checksums.sha256` file recording the digest of every file.
+pub struct EmbeddingModelCache {
+    pub model_dir: PathBuf,
+}
+
+impl Default for EmbeddingModelCache {
+    fn default() -> Self {
+        let model_dir = match (std::env::var_os("ACE_MODEL_DIR"), std::env::var_os("HOME")) {
+            (Some(dir), _) => PathBuf::from(dir),
+            (None, Some(home)) => Path::new(&home).join(".cache").join("ace").join("models"),
+            (None, None) => PathBuf::from(".ace").join("models"),
+        };
+        Self { model_dir }
+    }
+}
+
+impl EmbeddingModelCache {
+    pub fn new(model_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            model_dir: model_dir.into(),
+        }
+    }
+
+    /// Returns the directory holding `model_name`, downloading any file that
+    /// is missing or no longer matches its recorded checksum.
+    pub async fn ensure_model(&self, model_name: &str) -> Result<PathBuf> {
+        let model = KNOWN_MODELS
+            .iter()
+            .find(|m| m.name == model_name)
+            .ok_or_else(|| format!("Unknown embedding model: {}", model_name))?;
+        let dir = self.model_dir.join(model.name);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+
+        let mut checksums = read_checksums(&dir.join(CHECKSUM_FILE));
+        let mut downloaded = 0;
+        for file in model.files {
+            let path = dir.join(file);
+            if let (Ok(bytes), Some(expected)) = (std::fs::read(&path), checksums.get(*file)) {
+                if sha256_hex(&bytes) == *expected {
+                    continue;
+                }
+                log_warning(&format!("{} is corrupted, downloading it again", path.display()));
+            }
+
+            let url = format!("{}/{}/resolve/main/{}", HUGGINGFACE_URL, model.repo, file);
+            let (bytes, published) = download(&url, file).await?;
+            let digest = sha256_hex(&bytes);
+            if let Some(published) = published {
+                if published != digest {
+                    return Err(format!(
+                        "Checksum mismatch for {}: expected {}, got {}",
+                        file, published, digest
+                    ));
+                }
+            }
+
+            let partial = path.with_extension("part");
+            std::fs::write(&partial, &bytes).map_err(|e| format!("Cannot write {}: {}", partial.display(), e))?;
+            std::fs::rename(&partial, &path).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+            checksums.insert(file.to_string(), digest);
+            downloaded += 1;
+        }
+
+        if downloaded > 0 {
+            write_checksums(&dir.join(CHECKSUM_FILE), &checksums)?;
+            log_success(&format!("Embedding model {} ready in {}", model.name, dir.display()));
+        }
+        Ok(dir)
+    }
+}
+
+/// Downloads `url` with a progress bar. Also returns the SHA-256 HuggingFace
+/// publishes for LFS files (`X-Linked-Etag`), when there is one.
+async fn download(url: &str, label: &str) -> Result<(Vec<u8>, Option<String>)> {
+    let resp = reqwest::get(url).await.map_err(|e| format!("Download failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Download of {} failed: HTTP {}", url, resp.status()));
+    }
+    let published = resp
+        .headers()
+        .get("x-linked-etag")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim_matches('"').to_lowercase())
+        .filter(|value| value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()));
+
+    log_info(&format!("Downloading {}", label));
+    let bar = ProgressBar::new(resp.content_length().unwrap_or(0));
+    bar.set_style(
+        ProgressStyle::with_template("{bar:40.cyan/blue} {bytes:>10}/{total_bytes} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    bar.set_message(label.to_string());
+
+    let mut bytes = Vec::new();
+    let mut chunks = resp.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                bar.abandon();
+                return Err(format!("Download of {} interrupted: {}", label, e));
+            }
+        };
+        bytes.extend_from_slice(&chunk);
+        bar.inc(chunk.len() as u64);
+    }
+    bar.finish_and_clear();
+    Ok((bytes, published))
+}
+
+// One `<sha256>  <file>` line per file, the format `sha256sum -c` accepts.
+fn read_checksums(path: &Path) -> BTreeMap<String, String> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(digest, file)| (file.to_string(), digest.to_string()))
+        .collect()
+}
+
+fn write_checksums(path: &Path, checksums: &BTreeMap<String, String>) -> Result<()> {
+    let contents: String = checksums
+        .iter()
+        .map(|(file, digest)| format!("{}  {}\n", digest, file))
+        .collect();
+    std::fs::write(path, contents).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
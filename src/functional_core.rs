@@ -3,40 +3,166 @@
 use crate::types::*;
 use chrono::Utc;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use unicode_normalization::UnicodeNormalization;
 use uuid::Uuid;
 
+// Bullet content normalization
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BulletNormalizer;
+
+impl BulletNormalizer {
+    pub fn normalize(&self, content: &str) -> String {
+        let spaces_re = Regex::new(r"(\S)[ \t]{2,}").unwrap();
+        let blank_lines_re = Regex::new(r"\n[ \t]*\n(?:[ \t]*\n)+").unwrap();
+        let ending_re = Regex::new(r"[.!?]+$").unwrap();
+
+        // Runs of spaces are collapsed after the first non-space character,
+        // so leading indentation (e.g. code) is preserved.
+        let text = content.trim();
+        let text = spaces_re.replace_all(text, "$1 ");
+        let text = blank_lines_re.replace_all(&text, "\n\n");
+
+        let mut text = match ending_re.find(&text) {
+            Some(m) => {
+                let last = m.as_str().chars().last().unwrap_or('.');
+                let ending = if last == '.' { '.' } else { last };
+                format!("{}{}", &text[..m.start()], ending)
+            }
+            None if text.chars().last().is_some_and(|c| c.is_alphanumeric()) => format!("{}.", text),
+            None => text.to_string(),
+        };
+
+        if let Some(first) = text.chars().next() {
+            if first.is_lowercase() {
+                text = first.to_uppercase().chain(text.chars().skip(1)).collect();
+            }
+        }
+        text
+    }
+}
+
+/// Fences, or Rust items such as `fn`, `pub struct`, `impl` and attributes.
+pub fn contains_code(content: &str) -> bool {
+    let code_re = Regex::new(r"```|\bfn\s+\w+\s*[(<]|\bpub\s+struct\b|\bimpl\b\s*[<\w]|#!?\[").unwrap();
+    code_re.is_match(content)
+}
+
+/// Language tag of the first fence, e.g. `rust` for ```` ```rust ````.
+pub fn code_fence_language(content: &str) -> Option<String> {
+    let fence_re = Regex::new(r"```[ \t]*([\w+#.-]+)").unwrap();
+    fence_re.captures(content).map(|caps| caps[1].to_lowercase())
+}
+
+// Code keeps its exact layout: prose normalization would recapitalize it or
+// append a period.
+fn bullet_content(content: &str) -> String {
+    if contains_code(content) {
+        content.trim_start_matches(['\n', '\r']).trim_end().to_string()
+    } else {
+        BulletNormalizer.normalize(content)
+    }
+}
+
 // Pure functions for context operations
 pub fn create_bullet(content: String, tags: Vec<String>) -> ContextBullet {
+    let is_code = contains_code(&content);
+    let mut tags = tags;
+    if is_code && !tags.iter().any(|t| t == "code") {
+        tags.push("code".to_string());
+    }
     ContextBullet {
         id: Uuid::new_v4().to_string(),
-        content,
+        content: bullet_content(&content),
+        code_language: if is_code { code_fence_language(&content) } else { None },
         helpful_count: 0,
         harmful_count: 0,
+        helpful_votes: GCounter::default(),
+        harmful_votes: GCounter::default(),
         created_at: Utc::now(),
         tags,
+        expires_at: None,
+        pinned: false,
+        access_count: 0,
+        metadata: HashMap::new(),
+        content_history: Vec::new(),
+        revision: 0,
     }
 }
 
-pub fn update_bullet_feedback(bullet: &ContextBullet, helpful: bool) -> ContextBullet {
+pub fn create_bullet_with_metadata(
+    content: String,
+    tags: Vec<String>,
+    meta: HashMap<String, serde_json::Value>,
+) -> ContextBullet {
     ContextBullet {
-        id: bullet.id.clone(),
-        content: bullet.content.clone(),
-        helpful_count: bullet.helpful_count + if helpful { 1 } else { 0 },
-        harmful_count: bullet.harmful_count + if helpful { 0 } else { 1 },
-        created_at: bullet.created_at,
-        tags: bullet.tags.clone(),
+        metadata: meta,
+        ..create_bullet(content, tags)
     }
 }
 
-pub fn score_bullet(bullet: &ContextBullet, query_words: &HashSet<String>) -> f64 {
-    let bullet_words: HashSet<String> = bullet
-        .content
-        .to_lowercase()
+pub fn create_bullet_ephemeral(content: String, tags: Vec<String>, ttl: chrono::Duration) -> ContextBullet {
+    ContextBullet {
+        expires_at: Some(Utc::now() + ttl),
+        ..create_bullet(content, tags)
+    }
+}
+
+pub fn update_bullet_feedback(bullet: &ContextBullet, helpful: bool) -> ContextBullet {
+    let mut rated = bullet.clone();
+    if helpful {
+        rated.helpful_count += 1;
+        rated.helpful_votes.increment();
+    } else {
+        rated.harmful_count += 1;
+        rated.harmful_votes.increment();
+    }
+    rated
+}
+
+/// Replaces the content, keeping at most `history_depth` previous versions.
+pub fn edit_bullet_content(bullet: &ContextBullet, new_content: &str, history_depth: usize) -> ContextBullet {
+    let mut edited = bullet.clone();
+    edited.content_history.push((bullet.content.clone(), Utc::now()));
+    let excess = edited.content_history.len().saturating_sub(history_depth);
+    edited.content_history.drain(..excess);
+    edited.content = bullet_content(new_content);
+    edited.revision += 1;
+    edited
+}
+
+/// Restores the content from `steps` edits ago, dropping those revisions.
+pub fn rollback_bullet_content(bullet: &ContextBullet, steps: usize) -> Result<ContextBullet> {
+    if steps == 0 || steps > bullet.content_history.len() {
+        return Err(format!(
+            "Cannot roll back {} steps; {} revisions available",
+            steps,
+            bullet.content_history.len()
+        ));
+    }
+    let mut rolled_back = bullet.clone();
+    let keep = rolled_back.content_history.len() - steps;
+    let (content, _) = rolled_back.content_history.drain(keep..).next().unwrap();
+    rolled_back.content = content;
+    rolled_back.revision += 1;
+    Ok(rolled_back)
+}
+
+// NFC-normalize and lowercase before splitting so that precomposed and
+// decomposed accents (common in Vietnamese/French input) compare equal.
+pub fn tokenize(text: &str) -> HashSet<String> {
+    text.nfc()
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
         .split_whitespace()
         .map(|s| s.to_string())
-        .collect();
-    
+        .collect()
+}
+
+pub fn score_bullet(bullet: &ContextBullet, query_words: &HashSet<String>) -> f64 {
+    let bullet_words = tokenize(&bullet.content);
+
     let overlap = query_words.intersection(&bullet_words).count() as f64;
     let feedback_score = (bullet.helpful_count - bullet.harmful_count) as f64 * 0.1;
     overlap + feedback_score
@@ -51,15 +177,19 @@ pub fn get_relevant_bullets(
         return Vec::new();
     }
 
-    let query_words: HashSet<String> = query
-        .to_lowercase()
-        .split_whitespace()
-        .map(|s| s.to_string())
-        .collect();
+    rank_bullets(context.bullets.values(), query, max_bullets)
+}
 
-    let mut scored: Vec<(f64, ContextBullet)> = context
-        .bullets
-        .values()
+fn rank_bullets<'a>(
+    bullets: impl Iterator<Item = &'a ContextBullet>,
+    query: &str,
+    max_bullets: usize,
+) -> Vec<ContextBullet> {
+    let query_words = tokenize(query);
+    let now = Utc::now();
+
+    let mut scored: Vec<(f64, ContextBullet)> = bullets
+        .filter(|b| !b.is_expired(now))
         .map(|b| (score_bullet(b, &query_words), b.clone()))
         .collect();
 
@@ -72,6 +202,344 @@ pub fn get_relevant_bullets(
         .collect()
 }
 
+/// Exchanges `detect_conversation_topic` looks back over.
+pub const TOPIC_EXCHANGES: usize = 3;
+
+const TOPIC_TERMS: usize = 8;
+
+/// The most frequent non-stop-word terms of the given exchanges
+/// (`conversation` bullets, newest first), most frequent first.
+pub fn detect_conversation_topic(recent_messages: &[ContextBullet]) -> Vec<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for message in recent_messages.iter().take(TOPIC_EXCHANGES) {
+        for term in tokenize_terms(&message.content) {
+            if term.chars().count() > 2 && !ENGLISH_STOP_WORDS.contains(&term.as_str()) {
+                *counts.entry(term).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut terms: Vec<(String, usize)> = counts.into_iter().collect();
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    terms.into_iter().take(TOPIC_TERMS).map(|(term, _)| term).collect()
+}
+
+/// `get_relevant_bullets` restricted to bullets whose tags or content share
+/// a term with `topic`. Falls back to the whole context when the topic is
+/// empty or nothing on it matches the query, e.g. after a change of subject.
+pub fn get_topic_scoped_bullets(
+    context: &ContextState,
+    query: &str,
+    max_bullets: usize,
+    topic: &[String],
+) -> Vec<ContextBullet> {
+    let on_topic = |bullet: &ContextBullet| {
+        bullet.tags.iter().any(|tag| topic.contains(&tag.to_lowercase()))
+            || tokenize_terms(&bullet.content).iter().any(|term| topic.contains(term))
+    };
+    let scoped = rank_bullets(context.bullets.values().filter(|b| on_topic(b)), query, max_bullets);
+    if scoped.is_empty() {
+        get_relevant_bullets(context, query, max_bullets)
+    } else {
+        scoped
+    }
+}
+
+pub const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "has", "have", "how", "i",
+    "in", "is", "it", "its", "of", "on", "or", "that", "the", "this", "to", "was", "were",
+    "what", "when", "where", "which", "who", "why", "will", "with", "you",
+];
+
+/// Fraction of bullets a token must appear in before it is treated as a stop-word.
+pub const CORPUS_STOP_WORD_FRACTION: f64 = 0.5;
+
+// Below this size document frequencies are too noisy to derive stop-words from.
+const MIN_STOP_WORD_CORPUS: usize = 10;
+
+/// Like `tokenize`, but keeps repeats and splits on punctuation so term
+/// frequencies can be counted.
+pub fn tokenize_terms(text: &str) -> Vec<String> {
+    text.nfc()
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+pub fn derive_corpus_stop_words(
+    bullets: &HashMap<String, ContextBullet>,
+    percentile: f64,
+) -> HashSet<String> {
+    if bullets.len() < MIN_STOP_WORD_CORPUS {
+        return HashSet::new();
+    }
+
+    let mut document_frequency: HashMap<String, usize> = HashMap::new();
+    for bullet in bullets.values() {
+        let unique: HashSet<String> = tokenize_terms(&bullet.content).into_iter().collect();
+        for token in unique {
+            *document_frequency.entry(token).or_insert(0) += 1;
+        }
+    }
+
+    let total = bullets.len() as f64;
+    document_frequency
+        .into_iter()
+        .filter(|(_, df)| *df as f64 / total > percentile)
+        .map(|(token, _)| token)
+        .collect()
+}
+
+/// Term -> bullet postings used for BM25 ranking of context bullets.
+#[derive(Debug, Clone, Default)]
+pub struct InvertedIndex {
+    postings: HashMap<String, HashMap<String, usize>>,
+    doc_lengths: HashMap<String, usize>,
+    avg_doc_length: f64,
+}
+
+impl InvertedIndex {
+    const K1: f64 = 1.2;
+    const B: f64 = 0.75;
+
+    /// Indexes every bullet, skipping `stop_words` (or `ENGLISH_STOP_WORDS` when `None`).
+    pub fn build(bullets: &HashMap<String, ContextBullet>, stop_words: Option<&HashSet<String>>) -> Self {
+        let default_stop_words: HashSet<String>;
+        let stop_words = match stop_words {
+            Some(words) => words,
+            None => {
+                default_stop_words = ENGLISH_STOP_WORDS.iter().map(|s| s.to_string()).collect();
+                &default_stop_words
+            }
+        };
+
+        let mut index = Self::default();
+        for (id, bullet) in bullets {
+            let terms: Vec<String> = tokenize_terms(&bullet.content)
+                .into_iter()
+                .filter(|t| !stop_words.contains(t))
+                .collect();
+            index.doc_lengths.insert(id.clone(), terms.len());
+            for term in terms {
+                *index
+                    .postings
+                    .entry(term)
+                    .or_default()
+                    .entry(id.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+
+        if !index.doc_lengths.is_empty() {
+            index.avg_doc_length =
+                index.doc_lengths.values().sum::<usize>() as f64 / index.doc_lengths.len() as f64;
+        }
+        index
+    }
+
+    pub fn contains(&self, term: &str) -> bool {
+        self.postings.contains_key(term)
+    }
+
+    pub fn tokens(&self) -> HashSet<String> {
+        self.postings.keys().cloned().collect()
+    }
+
+    /// BM25 score per bullet id for every bullet matching at least one term.
+    pub fn bm25(&self, query_terms: &[String]) -> HashMap<String, f64> {
+        let total_docs = self.doc_lengths.len() as f64;
+        let mut scores: HashMap<String, f64> = HashMap::new();
+
+        let unique: HashSet<&String> = query_terms.iter().collect();
+        for term in unique {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let df = postings.len() as f64;
+            let idf = ((total_docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+
+            for (id, tf) in postings {
+                let tf = *tf as f64;
+                let doc_length = self.doc_lengths[id] as f64;
+                let norm = 1.0 - Self::B + Self::B * doc_length / self.avg_doc_length.max(1.0);
+                *scores.entry(id.clone()).or_insert(0.0) += idf * tf * (Self::K1 + 1.0) / (tf + Self::K1 * norm);
+            }
+        }
+        scores
+    }
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Replaces each query word missing from `known_tokens` with the closest
+/// known token within two edits. Words of three characters or fewer are left
+/// alone since almost anything is within two edits of them.
+pub fn correct_query(query: &str, known_tokens: &HashSet<String>) -> String {
+    const MAX_EDITS: usize = 2;
+
+    query
+        .split_whitespace()
+        .map(|word| {
+            let lower = word.nfc().flat_map(char::to_lowercase).collect::<String>();
+            if lower.chars().count() <= 3 || known_tokens.contains(&lower) {
+                return word.to_string();
+            }
+            known_tokens
+                .iter()
+                .map(|token| (levenshtein(&lower, token), token))
+                .filter(|(distance, _)| *distance <= MAX_EDITS)
+                .min()
+                .map(|(_, token)| token.clone())
+                .unwrap_or_else(|| word.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Lowercased multi-word phrases wrapped in double quotes. A quoted single
+/// word is not a phrase and is left to normal term matching.
+pub fn extract_quoted_phrases(query: &str) -> Vec<String> {
+    let re = Regex::new(r#""([^"]+)""#).unwrap();
+    re.captures_iter(query)
+        .map(|cap| cap[1].split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase())
+        .filter(|phrase| phrase.contains(' '))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchQuery {
+    Term(String),
+    And(Box<SearchQuery>, Box<SearchQuery>),
+    Or(Box<SearchQuery>, Box<SearchQuery>),
+    Not(Box<SearchQuery>),
+}
+
+impl SearchQuery {
+    pub fn evaluate(&self, bullet: &ContextBullet) -> bool {
+        let words: HashSet<String> = tokenize_terms(&bullet.content).into_iter().collect();
+        self.matches(&words)
+    }
+
+    fn matches(&self, words: &HashSet<String>) -> bool {
+        match self {
+            SearchQuery::Term(term) => tokenize_terms(term).iter().all(|t| words.contains(t)),
+            SearchQuery::And(a, b) => a.matches(words) && b.matches(words),
+            SearchQuery::Or(a, b) => a.matches(words) || b.matches(words),
+            SearchQuery::Not(inner) => !inner.matches(words),
+        }
+    }
+
+    /// Terms that are not under a `NOT`; these drive BM25 ranking.
+    pub fn positive_terms(&self) -> Vec<String> {
+        match self {
+            SearchQuery::Term(term) => tokenize_terms(term),
+            SearchQuery::And(a, b) | SearchQuery::Or(a, b) => {
+                let mut terms = a.positive_terms();
+                terms.extend(b.positive_terms());
+                terms
+            }
+            SearchQuery::Not(_) => Vec::new(),
+        }
+    }
+}
+
+/// Parses `AND`/`OR`/`NOT` (uppercase) with parentheses; adjacent terms are
+/// implicitly ANDed. Returns `None` when the query uses no operators or is
+/// malformed, so callers can fall back to plain ranking.
+pub fn parse_search_query(raw: &str) -> Option<SearchQuery> {
+    let spaced = raw.replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+    if !tokens.iter().any(|t| matches!(*t, "AND" | "OR" | "NOT" | "(" | ")")) {
+        return None;
+    }
+
+    let mut pos = 0;
+    let query = parse_or(&tokens, &mut pos)?;
+    (pos == tokens.len()).then_some(query)
+}
+
+fn parse_or(tokens: &[&str], pos: &mut usize) -> Option<SearchQuery> {
+    let mut left = parse_and(tokens, pos)?;
+    while tokens.get(*pos) == Some(&"OR") {
+        *pos += 1;
+        let right = parse_and(tokens, pos)?;
+        left = SearchQuery::Or(Box::new(left), Box::new(right));
+    }
+    Some(left)
+}
+
+fn parse_and(tokens: &[&str], pos: &mut usize) -> Option<SearchQuery> {
+    let mut left = parse_unary(tokens, pos)?;
+    loop {
+        match tokens.get(*pos) {
+            Some(&"AND") => *pos += 1,
+            Some(&"OR") | Some(&")") | None => return Some(left),
+            Some(_) => {}
+        }
+        let right = parse_unary(tokens, pos)?;
+        left = SearchQuery::And(Box::new(left), Box::new(right));
+    }
+}
+
+fn parse_unary(tokens: &[&str], pos: &mut usize) -> Option<SearchQuery> {
+    let token = *tokens.get(*pos)?;
+    *pos += 1;
+    match token {
+        "NOT" => Some(SearchQuery::Not(Box::new(parse_unary(tokens, pos)?))),
+        "(" => {
+            let inner = parse_or(tokens, pos)?;
+            if tokens.get(*pos) != Some(&")") {
+                return None;
+            }
+            *pos += 1;
+            Some(inner)
+        }
+        "AND" | "OR" | ")" => None,
+        term => Some(SearchQuery::Term(term.to_string())),
+    }
+}
+
+/// Content address of a bullet: SHA-256 of its NFC, lowercased,
+/// whitespace-collapsed content.
+pub fn content_id(content: &str) -> String {
+    let normalized = content.nfc().flat_map(char::to_lowercase).collect::<String>();
+    let normalized = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+    sha256_hex(normalized.as_bytes())
+}
+
+/// Re-ids the delta's bullets by `content_id`, dropping those whose content
+/// is already in `context` (or earlier in the delta).
+pub fn content_address_delta(context: &ContextState, delta: &DeltaUpdate) -> DeltaUpdate {
+    let mut seen = HashSet::new();
+    let bullets = delta
+        .bullets
+        .iter()
+        .map(|bullet| ContextBullet {
+            id: content_id(&bullet.content),
+            ..bullet.clone()
+        })
+        .filter(|bullet| !context.bullets.contains_key(&bullet.id) && seen.insert(bullet.id.clone()))
+        .collect();
+    DeltaUpdate {
+        bullets,
+        timestamp: delta.timestamp,
+    }
+}
+
 pub fn merge_delta(context: &ContextState, delta: &DeltaUpdate) -> ContextState {
     let mut new_bullets = context.bullets.clone();
 
@@ -85,9 +553,11 @@ pub fn merge_delta(context: &ContextState, delta: &DeltaUpdate) -> ContextState
         }
     }
 
+    let mut version = context.version.clone();
+    version.increment();
     ContextState {
         bullets: new_bullets,
-        version: context.version + 1,
+        version,
     }
 }
 
@@ -95,20 +565,10 @@ pub fn find_duplicate_bullet(
     new_bullet: &ContextBullet,
     existing: &HashMap<String, ContextBullet>,
 ) -> Option<String> {
-    let new_words: HashSet<String> = new_bullet
-        .content
-        .to_lowercase()
-        .split_whitespace()
-        .map(|s| s.to_string())
-        .collect();
+    let new_words = tokenize(&new_bullet.content);
 
     for (id, bullet) in existing {
-        let existing_words: HashSet<String> = bullet
-            .content
-            .to_lowercase()
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
+        let existing_words = tokenize(&bullet.content);
 
         if !new_words.is_empty() && !existing_words.is_empty() {
             let overlap = new_words.intersection(&existing_words).count();
@@ -121,9 +581,228 @@ pub fn find_duplicate_bullet(
     None
 }
 
+pub fn jaccard_similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<String> = tokenize_terms(a).into_iter().collect();
+    let b: HashSet<String> = tokenize_terms(b).into_iter().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+pub fn cosine_similarity(a: &str, b: &str) -> f64 {
+    let frequencies = |text: &str| {
+        let mut counts: HashMap<String, f64> = HashMap::new();
+        for term in tokenize_terms(text) {
+            *counts.entry(term).or_insert(0.0) += 1.0;
+        }
+        counts
+    };
+    let (a, b) = (frequencies(a), frequencies(b));
+
+    let dot: f64 = a.iter().filter_map(|(t, x)| b.get(t).map(|y| x * y)).sum();
+    let norm = |v: &HashMap<String, f64>| v.values().map(|x| x * x).sum::<f64>().sqrt();
+    let denominator = norm(&a) * norm(&b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+// How well each bullet retrieves the other relative to retrieving itself,
+// taking the weaker direction so short bullets inside long ones don't score 1.0.
+fn bm25_similarity(index: &InvertedIndex, a: &ContextBullet, b: &ContextBullet) -> f64 {
+    let directed = |from: &ContextBullet, to: &ContextBullet| {
+        let scores = index.bm25(&tokenize_terms(&from.content));
+        match (scores.get(&from.id), scores.get(&to.id)) {
+            (Some(own), Some(other)) if *own > 0.0 => (other / own).min(1.0),
+            _ => 0.0,
+        }
+    };
+    directed(a, b).min(directed(b, a))
+}
+
+/// Pairs of bullets sharing a tag whose best Jaccard/cosine/BM25 similarity
+/// reaches `threshold`. Only bullets within the same tag group are compared.
+pub fn find_near_duplicates(bullets: &HashMap<String, ContextBullet>, threshold: f64) -> Vec<DuplicatePair> {
+    let mut groups: HashMap<&str, Vec<&ContextBullet>> = HashMap::new();
+    for bullet in bullets.values() {
+        for tag in &bullet.tags {
+            groups.entry(tag.as_str()).or_default().push(bullet);
+        }
+    }
+
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    let mut pairs = Vec::new();
+    for group in groups.values() {
+        let group_map: HashMap<String, ContextBullet> =
+            group.iter().map(|b| (b.id.clone(), (*b).clone())).collect();
+        let index = InvertedIndex::build(&group_map, Some(&HashSet::new()));
+
+        for (i, a) in group.iter().enumerate() {
+            for b in &group[i + 1..] {
+                let key = if a.id < b.id {
+                    (a.id.clone(), b.id.clone())
+                } else {
+                    (b.id.clone(), a.id.clone())
+                };
+                if seen.contains(&key) {
+                    continue;
+                }
+
+                let (similarity, method) = [
+                    (jaccard_similarity(&a.content, &b.content), SimilarityMethod::Jaccard),
+                    (cosine_similarity(&a.content, &b.content), SimilarityMethod::Cosine),
+                    (bm25_similarity(&index, a, b), SimilarityMethod::BM25),
+                ]
+                .into_iter()
+                .max_by(|x, y| x.0.partial_cmp(&y.0).unwrap())
+                .unwrap();
+
+                if similarity >= threshold {
+                    seen.insert(key.clone());
+                    pairs.push(DuplicatePair {
+                        bullet_a_id: key.0,
+                        bullet_b_id: key.1,
+                        similarity,
+                        method,
+                    });
+                }
+            }
+        }
+    }
+
+    pairs.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap());
+    pairs
+}
+
+/// Word-level diff from `a` to `b`. The LCS is found with Hunt-Szymanski,
+/// which only visits matching word pairs, so long mostly-different bullets
+/// stay cheap compared to the full DP table.
+pub fn diff_strings(a: &str, b: &str) -> Vec<DiffToken> {
+    let a: Vec<&str> = a.split_whitespace().collect();
+    let b: Vec<&str> = b.split_whitespace().collect();
+
+    let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (j, word) in b.iter().enumerate() {
+        positions.entry(word).or_default().push(j);
+    }
+
+    // thresholds[k]: smallest b index ending a common subsequence of length k + 1
+    let mut thresholds: Vec<usize> = Vec::new();
+    let mut links: Vec<usize> = Vec::new();
+    let mut nodes: Vec<(usize, usize, Option<usize>)> = Vec::new();
+    for (i, word) in a.iter().enumerate() {
+        let Some(matches) = positions.get(word) else {
+            continue;
+        };
+        // Descending j so one a-word can't extend a chain it just created
+        for &j in matches.iter().rev() {
+            let k = thresholds.partition_point(|&t| t < j);
+            if k < thresholds.len() && thresholds[k] == j {
+                continue;
+            }
+            let previous = k.checked_sub(1).map(|p| links[p]);
+            nodes.push((i, j, previous));
+            if k == thresholds.len() {
+                thresholds.push(j);
+                links.push(nodes.len() - 1);
+            } else {
+                thresholds[k] = j;
+                links[k] = nodes.len() - 1;
+            }
+        }
+    }
+
+    let mut common = Vec::new();
+    let mut cursor = links.last().copied();
+    while let Some(node) = cursor {
+        let (i, j, previous) = nodes[node];
+        common.push((i, j));
+        cursor = previous;
+    }
+    common.reverse();
+
+    let mut tokens = Vec::new();
+    let (mut ai, mut bj) = (0, 0);
+    for (i, j) in common.into_iter().chain(std::iter::once((a.len(), b.len()))) {
+        tokens.extend(a[ai..i].iter().map(|w| DiffToken::Deleted(w.to_string())));
+        tokens.extend(b[bj..j].iter().map(|w| DiffToken::Inserted(w.to_string())));
+        if i < a.len() {
+            tokens.push(DiffToken::Same(a[i].to_string()));
+        }
+        ai = i + 1;
+        bj = j + 1;
+    }
+    tokens
+}
+
+/// Edges `(a, b)` meaning question `b` builds on question `a`: at least half
+/// of `a`'s content terms reappear in `b`, and `b` is the more specific
+/// question (strictly more terms). The size ordering keeps the graph acyclic.
+pub fn detect_dependencies(questions: &[String]) -> Vec<(usize, usize)> {
+    let terms: Vec<HashSet<String>> = questions
+        .iter()
+        .map(|q| {
+            tokenize_terms(q)
+                .into_iter()
+                .filter(|t| !ENGLISH_STOP_WORDS.contains(&t.as_str()))
+                .collect()
+        })
+        .collect();
+
+    let mut edges = Vec::new();
+    for (a, a_terms) in terms.iter().enumerate() {
+        if a_terms.is_empty() {
+            continue;
+        }
+        for (b, b_terms) in terms.iter().enumerate() {
+            if a == b || b_terms.len() <= a_terms.len() {
+                continue;
+            }
+            let shared = a_terms.intersection(b_terms).count();
+            if shared * 2 >= a_terms.len() {
+                edges.push((a, b));
+            }
+        }
+    }
+    edges
+}
+
+/// Kahn's algorithm; ties keep the original order. Nodes caught in a cycle
+/// are appended in index order rather than dropped.
+pub fn topological_order(count: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut in_degree = vec![0usize; count];
+    for &(_, to) in edges {
+        in_degree[to] += 1;
+    }
+
+    let mut order = Vec::with_capacity(count);
+    let mut ready: std::collections::BTreeSet<usize> = (0..count).filter(|&i| in_degree[i] == 0).collect();
+    while let Some(next) = ready.pop_first() {
+        order.push(next);
+        for &(from, to) in edges {
+            if from == next {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.insert(to);
+                }
+            }
+        }
+    }
+
+    if order.len() < count {
+        let placed: HashSet<usize> = order.iter().copied().collect();
+        order.extend((0..count).filter(|i| !placed.contains(i)));
+    }
+    order
+}
+
 pub fn parse_trajectory_response(query: String, response: &str) -> Trajectory {
     let steps_re = Regex::new(r"(?i)STEPS:\s*\[(.*?)\]").unwrap();
-    let outcome_re = Regex::new(r"(?i)OUTCOME:\s*(.+?)(?=\n|$)").unwrap();
+    let outcome_re = Regex::new(r"(?im)OUTCOME:\s*(.+?)$").unwrap();
     let success_re = Regex::new(r"(?i)SUCCESS:\s*(true|false)").unwrap();
 
     let steps = if let Some(caps) = steps_re.captures(response) {
@@ -157,15 +836,30 @@ pub fn parse_trajectory_response(query: String, response: &str) -> Trajectory {
         .unwrap_or(true);
 
     Trajectory {
+        id: Uuid::new_v4().to_string(),
         query,
         steps,
         outcome,
         success,
         used_bullets: Vec::new(),
         feedback: None,
+        quality_score: None,
     }
 }
 
+/// Reads `ACCURACY:N COMPLETENESS:N CONCISENESS:N`; every score must be 1-5.
+pub fn parse_quality_assessment(response: &str) -> Option<QualityAssessment> {
+    let score = |name: &str| -> Option<u8> {
+        let re = Regex::new(&format!(r"(?i)\b{}\s*:\s*([1-5])\b", name)).unwrap();
+        re.captures(response)?[1].parse().ok()
+    };
+    Some(QualityAssessment {
+        accuracy: score("accuracy")?,
+        completeness: score("completeness")?,
+        conciseness: score("conciseness")?,
+    })
+}
+
 pub fn parse_insights_response(response: &str, source_id: String) -> Vec<Insight> {
     let re = Regex::new(r"(?i)\[Content:\s*(.+?);\s*Type:\s*(.+?);\s*Confidence:\s*([0-9.]+)\]")
         .unwrap();
@@ -198,6 +892,43 @@ pub fn parse_insights_response(response: &str, source_id: String) -> Vec<Insight
     insights
 }
 
+pub fn parse_critique_score(response: &str) -> Option<f64> {
+    let re = Regex::new(r"(?i)SCORE:\s*([0-9]+(?:\.[0-9]+)?)").unwrap();
+    let number_re = Regex::new(r"\b([0-9]+(?:\.[0-9]+)?)\s*/\s*10\b").unwrap();
+
+    re.captures(response)
+        .or_else(|| number_re.captures(response))
+        .and_then(|caps| caps.get(1))
+        .and_then(|m| m.as_str().parse::<f64>().ok())
+        .filter(|score| (0.0..=10.0).contains(score))
+}
+
+/// Pulls the JSON document out of a model response, tolerating code fences
+/// and surrounding prose.
+pub fn extract_json(response: &str) -> Option<serde_json::Value> {
+    let trimmed = response.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Some(value);
+    }
+
+    let start = trimmed.find(['{', '['])?;
+    let end = trimmed.rfind(['}', ']'])?;
+    if end <= start {
+        return None;
+    }
+    serde_json::from_str(&trimmed[start..=end]).ok()
+}
+
+pub fn validate_json(value: &serde_json::Value, schema: &serde_json::Value) -> Result<()> {
+    let config = jsonschema_valid::Config::from_schema(schema, None)
+        .map_err(|e| format!("Invalid schema: {}", e))?;
+
+    jsonschema_valid::validate(&config, value).map_err(|errors| {
+        let messages: Vec<String> = errors.map(|e| e.to_string()).collect();
+        format!("Schema validation failed: {}", messages.join("; "))
+    })
+}
+
 pub fn insights_to_delta(insights: Vec<Insight>) -> DeltaUpdate {
     let bullets = insights
         .into_iter()
@@ -211,18 +942,214 @@ pub fn insights_to_delta(insights: Vec<Insight>) -> DeltaUpdate {
     }
 }
 
-pub fn build_context_prompt(bullets: &[ContextBullet]) -> String {
+/// Splits `text` into chunks of roughly `chunk_size` characters, breaking
+/// only after sentence-ending punctuation followed by whitespace. A single
+/// sentence longer than `chunk_size` becomes a chunk of its own.
+pub fn chunk_text(text: &str, chunk_size: usize) -> Vec<String> {
+    let boundary = Regex::new(r"[.!?]\s").unwrap();
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for m in boundary.find_iter(text) {
+        sentences.push(&text[start..m.start() + 1]);
+        start = m.end();
+    }
+    sentences.push(&text[start..]);
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for sentence in sentences.into_iter().map(str::trim).filter(|s| !s.is_empty()) {
+        if !current.is_empty() && current.chars().count() + 1 + sentence.chars().count() > chunk_size {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(sentence);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+pub fn compute_context_health(context: &ContextState) -> ContextHealth {
+    compute_context_health_weighted(context, &CuratorConfig::default().health_weights)
+}
+
+pub fn compute_context_health_weighted(context: &ContextState, weights: &HashMap<String, f64>) -> ContextHealth {
+    const FRESHNESS_HALF_LIFE_DAYS: f64 = 30.0;
+
+    let bullets: Vec<&ContextBullet> = context.bullets.values().collect();
     if bullets.is_empty() {
-        return "No previous context available.".to_string();
+        return ContextHealth::default();
     }
+    let total = bullets.len() as f64;
+    let now = Utc::now();
 
-    bullets
+    let freshness = bullets
         .iter()
         .map(|b| {
-            format!(
+            let age_days = (now - b.created_at).num_seconds().max(0) as f64 / 86_400.0;
+            0.5f64.powf(age_days / FRESHNESS_HALF_LIFE_DAYS)
+        })
+        .sum::<f64>()
+        / total;
+
+    let distinct_tags: HashSet<&String> = bullets.iter().flat_map(|b| &b.tags).collect();
+    let diversity = (distinct_tags.len() as f64 / total).min(1.0);
+
+    let helpfulness = bullets
+        .iter()
+        .map(|b| b.helpful_count.max(0) as f64 / (b.helpful_count.max(0) + b.harmful_count.max(0) + 1) as f64)
+        .sum::<f64>()
+        / total;
+
+    // Share of distinct terms in each bullet; repetitive bullets score low
+    let density = bullets
+        .iter()
+        .map(|b| {
+            let terms = tokenize_terms(&b.content);
+            if terms.is_empty() {
+                0.0
+            } else {
+                terms.iter().collect::<HashSet<_>>().len() as f64 / terms.len() as f64
+            }
+        })
+        .sum::<f64>()
+        / total;
+
+    let coverage = bullets.iter().filter(|b| b.helpful_count + b.harmful_count > 0).count() as f64 / total;
+
+    let breakdown: HashMap<String, f64> = [
+        ("freshness", freshness),
+        ("diversity", diversity),
+        ("helpfulness", helpfulness),
+        ("density", density),
+        ("coverage", coverage),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect();
+
+    let weight_sum: f64 = breakdown.keys().map(|k| weights.get(k).copied().unwrap_or(0.0)).sum();
+    let score = if weight_sum > 0.0 {
+        breakdown
+            .iter()
+            .map(|(k, v)| v * weights.get(k).copied().unwrap_or(0.0))
+            .sum::<f64>()
+            / weight_sum
+    } else {
+        0.0
+    };
+
+    ContextHealth { score, breakdown }
+}
+
+/// Rough token count (about four characters per token), good enough for
+/// budgeting without a model-specific tokenizer.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// What a dry-run backend answers instead of generating.
+pub fn dry_run_response(prompt: &str) -> String {
+    format!("DRY RUN: would generate response for prompt of {} tokens", estimate_tokens(prompt))
+}
+
+/// Splits the prompt `build_query_prompt` builds for `query` and `bullets`
+/// into its parts. The query segment also covers the prompt's own framing,
+/// so the segments add up to the whole prompt.
+pub fn context_window_view(
+    context: &ContextState,
+    query: &str,
+    bullets: &[ContextBullet],
+    forced_language: Option<&str>,
+    context_window: usize,
+    rich: bool,
+) -> ContextWindowView {
+    let prompt_tokens = estimate_tokens(&build_query_prompt(context, query, bullets, forced_language, rich));
+    let system_prompt_tokens = language_instruction(query, forced_language)
+        .map(|instruction| estimate_tokens(&instruction))
+        .unwrap_or(0);
+    let context_bullets_tokens = if bullets.is_empty() {
+        0
+    } else {
+        estimate_tokens(&build_context_prompt(bullets, rich))
+    };
+    let history = recent_conversation(context, 1);
+    let conversation_history_tokens = match history.first() {
+        None => 0,
+        Some(last) if is_continue_query(query) => estimate_tokens(&last.content),
+        Some(_) => estimate_tokens(&build_context_prompt(&history, rich)),
+    };
+    let query_tokens =
+        prompt_tokens.saturating_sub(system_prompt_tokens + context_bullets_tokens + conversation_history_tokens);
+
+    ContextWindowView {
+        total_tokens: context_window,
+        system_prompt_tokens,
+        context_bullets_tokens,
+        conversation_history_tokens,
+        query_tokens,
+        remaining_tokens: context_window.saturating_sub(prompt_tokens),
+    }
+}
+
+/// Drops Markdown syntax, keeping the text: fence lines, list markers,
+/// heading marks, emphasis, inline code ticks and link targets. Lines inside
+/// fences, and every line when `code` is set, only lose the fences.
+pub fn strip_markdown(text: &str, code: bool) -> String {
+    let block_re = Regex::new(r"^[ \t]*(?:[-*+]|\d+[.)]|#{1,6}|>)[ \t]+").unwrap();
+    let emphasis_re = Regex::new(r"(\*\*|__|\*|`)([^*`]+?)(\*\*|__|\*|`)").unwrap();
+    let link_re = Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").unwrap();
+
+    let mut in_fence = code;
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = code || !in_fence;
+            continue;
+        }
+        if in_fence {
+            lines.push(line.to_string());
+            continue;
+        }
+        let line = block_re.replace(line, "");
+        let line = emphasis_re.replace_all(&line, |caps: &regex::Captures| {
+            if caps[1] == caps[3] {
+                caps[2].to_string()
+            } else {
+                caps[0].to_string()
+            }
+        });
+        lines.push(link_re.replace_all(&line, "$1").into_owned());
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// `rich` keeps Markdown (and fences unfenced code bullets) so the model
+/// sees code as code and lists as lists; otherwise formatting is stripped
+/// to save tokens.
+pub fn build_context_prompt(bullets: &[ContextBullet], rich: bool) -> String {
+    if bullets.is_empty() {
+        return "No previous context available.".to_string();
+    }
+
+    bullets
+        .iter()
+        .map(|b| {
+            let is_code = b.tags.iter().any(|t| t == "code");
+            let content = if !rich {
+                strip_markdown(&b.content, is_code)
+            } else if is_code && !b.content.contains("```") {
+                format!("\n```{}\n{}\n```\n", b.code_language.as_deref().unwrap_or(""), b.content)
+            } else {
+                b.content.clone()
+            };
+            format!(
                 "[{}] {} (helpful: {}, harmful: {})",
                 &b.id[..8.min(b.id.len())],
-                b.content,
+                content,
                 b.helpful_count,
                 b.harmful_count
             )
@@ -230,3 +1157,1013 @@ pub fn build_context_prompt(bullets: &[ContextBullet]) -> String {
         .collect::<Vec<_>>()
         .join("\n")
 }
+
+pub fn detect_language(text: &str) -> Option<LanguageCode> {
+    let (mut cjk, mut cyrillic, mut vietnamese, mut diacritics) = (0, 0, 0, 0);
+
+    for c in text.chars() {
+        match c {
+            '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7AF}' => cjk += 1,
+            '\u{0400}'..='\u{04FF}' => cyrillic += 1,
+            'đ' | 'Đ' | 'ơ' | 'Ơ' | 'ư' | 'Ư' | '\u{1EA0}'..='\u{1EF9}' => vietnamese += 1,
+            '\u{00C0}'..='\u{024F}' => diacritics += 1,
+            _ => {}
+        }
+    }
+
+    if cjk > 0 {
+        Some(LanguageCode::Cjk)
+    } else if cyrillic > 0 {
+        Some(LanguageCode::Russian)
+    } else if vietnamese > 0 {
+        Some(LanguageCode::Vietnamese)
+    } else if diacritics > 0 {
+        Some(LanguageCode::European)
+    } else {
+        None
+    }
+}
+
+pub fn language_instruction(query: &str, forced_language: Option<&str>) -> Option<String> {
+    match forced_language {
+        Some(language) => Some(format!("Respond in {}.", language)),
+        None => detect_language(query).map(|_| "Respond in the same language as the query.".to_string()),
+    }
+}
+
+pub fn recent_conversation(context: &ContextState, n: usize) -> Vec<ContextBullet> {
+    let mut conv_bullets: Vec<_> = context
+        .bullets
+        .values()
+        .filter(|b| b.tags.contains(&"conversation".to_string()))
+        .collect();
+    conv_bullets.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+    conv_bullets.into_iter().take(n).cloned().collect()
+}
+
+/// Context bullets retrieved for a query prompt.
+pub const QUERY_CONTEXT_BULLETS: usize = 5;
+
+fn is_continue_query(query: &str) -> bool {
+    let query = query.trim().to_lowercase();
+    query == "continue" || query == "tiếp tục"
+}
+
+/// The part of `retrieved` that `build_query_prompt` sends: nothing when the
+/// query continues the last answer, and never the latest exchange, which the
+/// prompt already carries as the previous conversation.
+pub fn query_context_bullets(context: &ContextState, query: &str, retrieved: Vec<ContextBullet>) -> Vec<ContextBullet> {
+    let recent_conv = recent_conversation(context, 1);
+    if is_continue_query(query) && !recent_conv.is_empty() {
+        return Vec::new();
+    }
+    retrieved
+        .into_iter()
+        .filter(|b| recent_conv.iter().all(|recent| recent.id != b.id))
+        .collect()
+}
+
+/// Builds the exact prompt `process_query_stream` sends for `query`, with
+/// `bullets` (from `query_context_bullets`) as learned context.
+pub fn build_query_prompt(
+    context: &ContextState,
+    query: &str,
+    bullets: &[ContextBullet],
+    forced_language: Option<&str>,
+    rich: bool,
+) -> String {
+    let recent_conv = recent_conversation(context, 1);
+
+    let prompt = if is_continue_query(query) && !recent_conv.is_empty() {
+        let last_conv = &recent_conv[0].content;
+        format!(
+            "{}\n\nContinue from where you stopped. Do not repeat, just continue:",
+            last_conv
+        )
+    } else if !recent_conv.is_empty() || !bullets.is_empty() {
+        let mut sections = Vec::new();
+        if !bullets.is_empty() {
+            sections.push(format!("Relevant context:\n{}", build_context_prompt(bullets, rich)));
+        }
+        if !recent_conv.is_empty() {
+            sections.push(format!("Previous conversation:\n{}", build_context_prompt(&recent_conv, rich)));
+        }
+        format!("{}\n\nNew query: {}\n\nAnswer:", sections.join("\n\n"), query)
+    } else {
+        query.to_string()
+    };
+
+    match language_instruction(query, forced_language) {
+        Some(instruction) => format!("{}\n\n{}", prompt, instruction),
+        None => prompt,
+    }
+}
+
+// Response postprocessing
+pub trait PostProcessor {
+    fn process(&self, response: &str) -> String;
+}
+
+#[derive(Default)]
+pub struct PostProcessorChain {
+    pub processors: Vec<Box<dyn PostProcessor + Send + Sync>>,
+}
+
+impl PostProcessorChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, processor: impl PostProcessor + Send + Sync + 'static) -> Self {
+        self.processors.push(Box::new(processor));
+        self
+    }
+}
+
+impl PostProcessor for PostProcessorChain {
+    fn process(&self, response: &str) -> String {
+        self.processors
+            .iter()
+            .fold(response.to_string(), |text, p| p.process(&text))
+    }
+}
+
+pub struct TruncateProcessor(pub usize);
+
+impl PostProcessor for TruncateProcessor {
+    fn process(&self, response: &str) -> String {
+        response.chars().take(self.0).collect()
+    }
+}
+
+pub struct StripDisclaimerProcessor;
+
+impl PostProcessor for StripDisclaimerProcessor {
+    fn process(&self, response: &str) -> String {
+        let re = Regex::new(r"(?i)^\s*(as an ai\b|i am an ai\b|i'm an ai\b|i am a language model|as a language model)").unwrap();
+        response
+            .lines()
+            .filter(|line| !re.is_match(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Gives untagged opening code fences a `text` language tag.
+pub struct CodeFenceNormalizer;
+
+impl PostProcessor for CodeFenceNormalizer {
+    fn process(&self, response: &str) -> String {
+        let mut in_fence = false;
+        response
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                if !trimmed.starts_with("```") {
+                    return line.to_string();
+                }
+                let opening = !in_fence;
+                in_fence = !in_fence;
+                if opening && trimmed.trim_end() == "```" {
+                    format!("{}text", line.trim_end())
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub struct PiiRedactProcessor;
+
+impl PostProcessor for PiiRedactProcessor {
+    fn process(&self, response: &str) -> String {
+        let email_re = Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap();
+        let phone_re = Regex::new(r"\+?\d[\d\s().-]{7,}\d").unwrap();
+
+        let redacted = email_re.replace_all(response, "[REDACTED_EMAIL]");
+        phone_re.replace_all(&redacted, "[REDACTED_PHONE]").to_string()
+    }
+}
+
+// Context-grounded fact verification
+const NEGATIONS: &[&str] = &[
+    "not", "no", "never", "none", "cannot", "can't", "isn't", "aren't", "doesn't", "don't",
+    "didn't", "won't", "wasn't", "weren't", "shouldn't", "without",
+];
+
+fn has_negation(words: &HashSet<String>) -> bool {
+    NEGATIONS.iter().any(|n| words.contains(*n))
+}
+
+/// Heuristic: a sentence is a factual claim when it ends in a number or a
+/// capitalised word that isn't the sentence's first word.
+pub fn extract_claims(text: &str) -> Vec<Claim> {
+    let sentence_re = Regex::new(r"[^.!?\n]+").unwrap();
+
+    sentence_re
+        .find_iter(text)
+        .map(|m| m.as_str().trim())
+        .filter(|sentence| {
+            let words: Vec<&str> = sentence.split_whitespace().collect();
+            let last = match words.last() {
+                Some(w) => w.trim_matches(|c: char| !c.is_alphanumeric()),
+                None => return false,
+            };
+            let ends_in_number = last.chars().last().is_some_and(|c| c.is_ascii_digit());
+            let ends_in_proper_noun =
+                words.len() > 1 && last.chars().next().is_some_and(|c| c.is_uppercase());
+            ends_in_number || ends_in_proper_noun
+        })
+        .map(|sentence| Claim {
+            text: sentence.to_string(),
+        })
+        .collect()
+}
+
+/// Flags claims that share most of their words with a bullet but disagree on
+/// negation ("X is Y" vs "X is not Y").
+pub fn verify_response(response: &str, bullets: &[ContextBullet]) -> VerificationReport {
+    let claims = extract_claims(response);
+    let mut contradictions = Vec::new();
+
+    for claim in &claims {
+        let claim_words = tokenize(&claim.text);
+        let claim_terms: HashSet<&String> = claim_words
+            .iter()
+            .filter(|w| !NEGATIONS.contains(&w.as_str()))
+            .collect();
+        if claim_terms.is_empty() {
+            continue;
+        }
+
+        for bullet in bullets {
+            let bullet_words = tokenize(&bullet.content);
+            let overlap = claim_terms.iter().filter(|w| bullet_words.contains(**w)).count();
+            let similarity = overlap as f64 / claim_terms.len() as f64;
+
+            if similarity >= 0.5 && has_negation(&claim_words) != has_negation(&bullet_words) {
+                contradictions.push(Contradiction {
+                    claim: claim.text.clone(),
+                    bullet_id: bullet.id.clone(),
+                    bullet_content: bullet.content.clone(),
+                });
+            }
+        }
+    }
+
+    VerificationReport {
+        claims,
+        contradictions,
+    }
+}
+
+pub fn classify_query(query: &str) -> QueryClass {
+    const CODE_KEYWORDS: &[&str] = &[
+        "code", "function", "fn", "struct", "impl", "class", "compile", "compiler", "bug",
+        "debug", "refactor", "implement", "syntax", "regex", "sql", "api", "rust", "python",
+        "javascript", "typescript", "java", "golang",
+    ];
+    const CODE_SYMBOLS: &[&str] = &["```", "()", "{", "=>", "::", "#["];
+
+    let lower = query.trim().to_lowercase();
+    let words = tokenize(&lower);
+
+    if CODE_KEYWORDS.iter().any(|k| words.contains(*k)) || CODE_SYMBOLS.iter().any(|s| lower.contains(s)) {
+        QueryClass::Code
+    } else if ["write", "create", "imagine", "compose", "invent"].iter().any(|p| lower.starts_with(p)) {
+        QueryClass::Creative
+    } else {
+        // "what"/"who"/"when"/"how many" and anything unrecognised stay factual
+        QueryClass::Factual
+    }
+}
+
+/// Reads an Ollama `/api/show` response. Newer servers report the limit under
+/// `model_info["<arch>.context_length"]`; older ones only via `num_ctx` in `parameters`.
+pub fn parse_model_info(json: &serde_json::Value) -> Option<ModelInfo> {
+    let model_info = &json["model_info"];
+    let architecture = model_info["general.architecture"]
+        .as_str()
+        .or_else(|| json["details"]["family"].as_str())
+        .unwrap_or("unknown")
+        .to_string();
+
+    let context_length = json["parameters"]["context_length"]
+        .as_i64()
+        .or_else(|| model_info[format!("{}.context_length", architecture).as_str()].as_i64())
+        .or_else(|| {
+            json["parameters"].as_str()?.lines().find_map(|line| {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next()) {
+                    (Some("num_ctx"), Some(value)) => value.parse().ok(),
+                    _ => None,
+                }
+            })
+        })?;
+
+    let parameter_count = json["details"]["parameter_size"]
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| model_info["general.parameter_count"].as_u64().map(|n| n.to_string()))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // Servers that predate the `capabilities` list get a name-based guess for thinking.
+    let capabilities = match json["capabilities"].as_array() {
+        Some(list) => {
+            let has = |name: &str| list.iter().any(|c| c.as_str() == Some(name));
+            ModelCapabilities {
+                supports_thinking: has("thinking"),
+                supports_structured_output: has("completion"),
+                max_context: context_length as i32,
+            }
+        }
+        None => {
+            let family = architecture.to_lowercase();
+            ModelCapabilities {
+                supports_thinking: ["qwen3", "deepseek"].iter().any(|f| family.contains(f)),
+                supports_structured_output: true,
+                max_context: context_length as i32,
+            }
+        }
+    };
+
+    Some(ModelInfo {
+        context_length: context_length as i32,
+        parameter_count,
+        architecture,
+        capabilities,
+    })
+}
+
+/// One line of Ollama's streaming `/api/pull` output; errors surface as an
+/// `error: ...` status so the stream itself never fails.
+pub fn parse_pull_progress(json: &serde_json::Value) -> PullProgress {
+    if let Some(error) = json["error"].as_str() {
+        return PullProgress {
+            status: format!("error: {}", error),
+            percent: 0.0,
+        };
+    }
+
+    let status = json["status"].as_str().unwrap_or("").to_string();
+    let percent = match (json["completed"].as_f64(), json["total"].as_f64()) {
+        (Some(completed), Some(total)) if total > 0.0 => completed / total * 100.0,
+        _ if status == "success" => 100.0,
+        _ => 0.0,
+    };
+    PullProgress { status, percent }
+}
+
+fn alias_key(command: &str) -> &str {
+    command.trim().trim_start_matches('/')
+}
+
+/// Rejects alias chains that loop back on themselves (`a -> b -> a`).
+pub fn validate_aliases(aliases: &HashMap<String, String>) -> Result<()> {
+    for start in aliases.keys() {
+        let mut seen = HashSet::new();
+        let mut current = alias_key(start);
+        while let Some(next) = aliases.get(current) {
+            if !seen.insert(current) {
+                return Err(format!("Config error: alias cycle involving '{}'", start));
+            }
+            current = alias_key(next);
+        }
+    }
+    Ok(())
+}
+
+/// Quotes `value` as a single POSIX shell word.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub fn render_plugin_script(script: &str, query: &str, session_id: &str) -> String {
+    script
+        .replace("{query}", &shell_quote(query))
+        .replace("{session_id}", &shell_quote(session_id))
+}
+
+/// Plugin names are used as `/name`, so they must be a single word that
+/// doesn't collide with another plugin; `args_pattern` must compile.
+pub fn validate_command_plugins(plugins: &[CommandPlugin]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for plugin in plugins {
+        let name = plugin.name.trim_start_matches('/');
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Config error: invalid command plugin name '{}'", plugin.name));
+        }
+        if !seen.insert(name) {
+            return Err(format!("Config error: duplicate command plugin '{}'", name));
+        }
+        if !plugin.args_pattern.is_empty() {
+            Regex::new(&plugin.args_pattern).map_err(|e| {
+                format!("Config error: bad args_pattern for command plugin '{}': {}", name, e)
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the plugin `input` invokes and its (trimmed) arguments.
+pub fn match_command_plugin<'a>(input: &'a str, plugins: &'a [CommandPlugin]) -> Option<(&'a CommandPlugin, &'a str)> {
+    let rest = input.strip_prefix('/')?;
+    let (name, args) = rest.split_once(' ').unwrap_or((rest, ""));
+    plugins
+        .iter()
+        .find(|p| p.name.trim_start_matches('/') == name)
+        .map(|plugin| (plugin, args.trim()))
+}
+
+pub fn plugin_args_match(plugin: &CommandPlugin, args: &str) -> bool {
+    plugin.args_pattern.is_empty()
+        || Regex::new(&format!("^(?:{})$", plugin.args_pattern))
+            .map(|re| re.is_match(args))
+            .unwrap_or(false)
+}
+
+/// Expands a leading `/alias` (following chains) and keeps the arguments.
+pub fn expand_alias(input: &str, aliases: &HashMap<String, String>) -> String {
+    let Some(rest) = input.strip_prefix('/') else {
+        return input.to_string();
+    };
+    let (mut command, args) = rest.split_once(' ').unwrap_or((rest, ""));
+    let mut hops = 0;
+    while let Some(target) = aliases.get(command) {
+        command = alias_key(target);
+        hops += 1;
+        if hops > aliases.len() {
+            break; // validate_aliases should have caught this
+        }
+    }
+
+    if args.is_empty() {
+        format!("/{}", command)
+    } else {
+        format!("/{} {}", command, args)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+// Wraps the last two words before the final period (or the last two words
+// overall) in a cloze deletion.
+fn cloze_deletion(content: &str) -> String {
+    let trimmed = content.trim_end();
+    let (body, tail) = match trimmed.rfind('.') {
+        Some(end) => (&trimmed[..end], &trimmed[end..]),
+        None => (trimmed, ""),
+    };
+    let words: Vec<&str> = body.split_whitespace().collect();
+    if words.len() < 2 {
+        return format!("{{{{c1::{}}}}}{}", body.trim(), tail);
+    }
+    let (head, answer) = words.split_at(words.len() - 2);
+    let prefix = if head.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", head.join(" "))
+    };
+    format!("{}{{{{c1::{}}}}}{}", prefix, answer.join(" "), tail)
+}
+
+/// One `"front","back","tags"` row per bullet. Conversation bullets
+/// (`Q: ...\nA: ...`) become question/answer cards; anything else becomes a
+/// cloze card with an empty back.
+pub fn bullets_to_anki_csv(bullets: &[ContextBullet]) -> String {
+    bullets
+        .iter()
+        .map(|bullet| {
+            let qa = bullet
+                .content
+                .strip_prefix("Q:")
+                .and_then(|rest| rest.split_once("\nA:"))
+                .map(|(q, a)| (q.trim().to_string(), a.trim().to_string()));
+            let (front, back) = qa.unwrap_or_else(|| (cloze_deletion(&bullet.content), String::new()));
+            let tags = bullet
+                .tags
+                .iter()
+                .map(|t| t.split_whitespace().collect::<Vec<_>>().join("_"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("{},{},{}", csv_field(&front), csv_field(&back), csv_field(&tags))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `[[note]]` -> `note`, `[[note|alias]]` -> `alias`.
+pub fn strip_wiki_links(text: &str) -> String {
+    let re = Regex::new(r"\[\[([^\]|]+)(?:\|([^\]]+))?\]\]").unwrap();
+    re.replace_all(text, |caps: &regex::Captures| {
+        caps.get(2).or_else(|| caps.get(1)).map(|m| m.as_str().to_string()).unwrap_or_default()
+    })
+    .into_owned()
+}
+
+fn tag_slug(text: &str) -> String {
+    tokenize_terms(text).join("-")
+}
+
+/// Minimal YAML frontmatter reader for the keys ACE cares about: `tags`
+/// (inline list, comma-separated or `- item` lines) and `ace_ignore`.
+/// Returns (tags, ignore, body without frontmatter).
+pub fn parse_frontmatter(text: &str) -> (Vec<String>, bool, &str) {
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return (Vec::new(), false, text);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (Vec::new(), false, text);
+    };
+    let (yaml, body) = (&rest[..end], &rest[end + 4..]);
+    let body = body.split_once('\n').map(|(_, b)| b).unwrap_or("");
+
+    let mut tags = Vec::new();
+    let mut ignore = false;
+    let mut in_tags = false;
+    for line in yaml.lines() {
+        let trimmed = line.trim();
+        if in_tags {
+            if let Some(item) = trimmed.strip_prefix("- ") {
+                tags.push(item.trim().trim_matches(['"', '\'']).to_string());
+                continue;
+            }
+            in_tags = false;
+        }
+        if let Some(value) = trimmed.strip_prefix("tags:") {
+            let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+            if value.is_empty() {
+                in_tags = true;
+            } else {
+                tags.extend(
+                    value
+                        .split(',')
+                        .map(|t| t.trim().trim_matches(['"', '\'']).to_string())
+                        .filter(|t| !t.is_empty()),
+                );
+            }
+        } else if let Some(value) = trimmed.strip_prefix("ace_ignore:") {
+            ignore = value.trim() == "true";
+        }
+    }
+    let tags = tags.into_iter().map(|t| t.trim_start_matches('#').to_string()).collect();
+    (tags, ignore, body)
+}
+
+/// Splits a note into `(content, tags)` per `##` section; text before the
+/// first heading is kept under `title`. `None` if the note opts out with
+/// `ace_ignore: true`.
+pub fn parse_obsidian_note(title: &str, text: &str) -> Option<Vec<(String, Vec<String>)>> {
+    let (file_tags, ignore, body) = parse_frontmatter(text);
+    if ignore {
+        return None;
+    }
+
+    let mut sections: Vec<(String, Vec<String>)> = Vec::new();
+    let mut heading = title.to_string();
+    let mut lines: Vec<&str> = Vec::new();
+    let mut flush = |heading: &str, lines: &mut Vec<&str>| {
+        let content = strip_wiki_links(lines.join("\n").trim());
+        lines.clear();
+        if content.is_empty() {
+            return;
+        }
+        let mut tags = file_tags.clone();
+        let heading_tag = tag_slug(heading);
+        if !heading_tag.is_empty() && !tags.contains(&heading_tag) {
+            tags.push(heading_tag);
+        }
+        sections.push((content, tags));
+    };
+
+    for line in body.lines() {
+        if let Some(next) = line.strip_prefix("## ") {
+            flush(&heading, &mut lines);
+            heading = strip_wiki_links(next.trim());
+        } else {
+            lines.push(line);
+        }
+    }
+    flush(&heading, &mut lines);
+    Some(sections)
+}
+
+/// Renders bullets as Obsidian notes, one `(file_stem, markdown)` per
+/// top-level tag (`uncategorized` for untagged bullets). Per-bullet metadata
+/// is kept in the frontmatter under `ace_bullets`.
+pub fn bullets_to_obsidian_notes(bullets: &[ContextBullet]) -> Vec<(String, String)> {
+    let mut groups: BTreeMap<String, Vec<&ContextBullet>> = BTreeMap::new();
+    for bullet in bullets {
+        let group = bullet
+            .tags
+            .first()
+            .map(|t| tag_slug(t))
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| "uncategorized".to_string());
+        groups.entry(group).or_default().push(bullet);
+    }
+
+    groups
+        .into_iter()
+        .map(|(group, mut bullets)| {
+            bullets.sort_by_key(|b| b.created_at);
+            let mut tags: Vec<&str> = Vec::new();
+            for tag in bullets.iter().flat_map(|b| &b.tags) {
+                if !tags.contains(&tag.as_str()) {
+                    tags.push(tag);
+                }
+            }
+
+            let mut note = String::from("---\n");
+            note.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+            note.push_str("ace_bullets:\n");
+            for bullet in &bullets {
+                note.push_str(&format!("  - ace_bullet_id: {}\n", bullet.id));
+                note.push_str(&format!("    helpful: {}\n", bullet.helpful_count));
+                note.push_str(&format!("    harmful: {}\n", bullet.harmful_count));
+                note.push_str(&format!("    created_at: {}\n", bullet.created_at.to_rfc3339()));
+            }
+            note.push_str("---\n");
+            for bullet in &bullets {
+                let title: String = bullet.content.lines().next().unwrap_or("").chars().take(60).collect();
+                note.push_str(&format!("\n## {}\n\n{}\n", title.trim(), bullet.content.trim()));
+            }
+            (group, note)
+        })
+        .collect()
+}
+
+/// FNV-1a over content and tags; stable across machines and Rust versions,
+/// unlike `DefaultHasher`.
+pub fn bullet_content_hash(bullet: &ContextBullet) -> u64 {
+    std::iter::once(bullet.content.as_str())
+        .chain(bullet.tags.iter().map(String::as_str))
+        .flat_map(|part| part.bytes().chain(std::iter::once(0)))
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Grows with every change to a bullet: content edits and rollbacks bump
+/// `revision`, feedback bumps one of the counts.
+pub fn bullet_version(bullet: &ContextBullet) -> i32 {
+    bullet.revision as i32 + bullet.helpful_count + bullet.harmful_count
+}
+
+pub fn compute_sync_manifest(context: &ContextState) -> SyncManifest {
+    let mut entries: Vec<ManifestEntry> = context
+        .bullets
+        .values()
+        .map(|bullet| ManifestEntry {
+            id: bullet.id.clone(),
+            content_hash: bullet_content_hash(bullet),
+            version: bullet_version(bullet),
+        })
+        .collect();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    SyncManifest {
+        entries,
+        clock: context.version.clone(),
+    }
+}
+
+/// Remote bullets missing locally, or changed with a newer version, are
+/// fetched. A changed bullet whose local version is the same or newer is a
+/// conflict and is left alone.
+pub fn plan_sync(local: &SyncManifest, remote: &SyncManifest) -> SyncPlan {
+    let local: HashMap<&str, &ManifestEntry> = local.entries.iter().map(|e| (e.id.as_str(), e)).collect();
+    let mut plan = SyncPlan::default();
+    for entry in &remote.entries {
+        match local.get(entry.id.as_str()) {
+            None => plan.fetch.push(entry.id.clone()),
+            Some(mine) if mine.content_hash == entry.content_hash && mine.version == entry.version => {}
+            Some(mine) if entry.version > mine.version => plan.fetch.push(entry.id.clone()),
+            Some(_) => plan.conflicts += 1,
+        }
+    }
+    plan
+}
+
+/// Joins two (count, votes) G-Counters. Feedback the votes do not account
+/// for (counted before per-node votes existed, or set directly as by
+/// compaction) is one more node's entry, merged by maximum like the rest
+/// and kept only in the count.
+fn merge_votes(ours: (i32, &GCounter), theirs: (i32, &GCounter)) -> (i32, GCounter) {
+    let unattributed = |(count, votes): (i32, &GCounter)| (count.max(0) as u64).saturating_sub(votes.total());
+    let votes = ours.1.merge(theirs.1);
+    let count = votes.total() + unattributed(ours).max(unattributed(theirs));
+    (count as i32, votes)
+}
+
+/// State-based CRDT merge; commutative, associative and idempotent.
+/// Content and tags are a last-write-wins register per bullet id, keyed on
+/// `revision` then `created_at` (ties broken by content so both sides pick
+/// the same winner).
+/// Feedback is a G-Counter per bullet: each node's votes are merged by
+/// maximum and the counts are their sum, so concurrent ratings from
+/// different instances add up.
+pub fn crdt_merge(a: &ContextState, b: &ContextState) -> ContextState {
+    let mut bullets = a.bullets.clone();
+    for (id, theirs) in &b.bullets {
+        let merged = match bullets.get(id) {
+            None => theirs.clone(),
+            Some(ours) => {
+                let newer = |x: &ContextBullet| (x.revision, x.created_at, x.content.clone(), x.tags.clone());
+                let mut winner = if newer(theirs) > newer(ours) { theirs.clone() } else { ours.clone() };
+                (winner.helpful_count, winner.helpful_votes) =
+                    merge_votes((ours.helpful_count, &ours.helpful_votes), (theirs.helpful_count, &theirs.helpful_votes));
+                (winner.harmful_count, winner.harmful_votes) =
+                    merge_votes((ours.harmful_count, &ours.harmful_votes), (theirs.harmful_count, &theirs.harmful_votes));
+                winner
+            }
+        };
+        bullets.insert(id.clone(), merged);
+    }
+    ContextState {
+        bullets,
+        version: a.version.merge(&b.version),
+    }
+}
+
+/// Groups unpinned bullets by their top tag: of a bullet's tags, the one
+/// most bullets share (alphabetically first on ties). Untagged bullets are
+/// left out.
+pub fn group_by_top_tag(context: &ContextState) -> BTreeMap<String, Vec<&ContextBullet>> {
+    let mut tag_counts: HashMap<&str, usize> = HashMap::new();
+    for bullet in context.bullets.values() {
+        for tag in &bullet.tags {
+            *tag_counts.entry(tag).or_default() += 1;
+        }
+    }
+
+    let mut groups: BTreeMap<String, Vec<&ContextBullet>> = BTreeMap::new();
+    for bullet in context.bullets.values().filter(|b| !b.pinned) {
+        let top = bullet
+            .tags
+            .iter()
+            .max_by_key(|tag| (tag_counts[tag.as_str()], std::cmp::Reverse(tag.as_str())));
+        if let Some(tag) = top {
+            groups.entry(tag.clone()).or_default().push(bullet);
+        }
+    }
+    for group in groups.values_mut() {
+        group.sort_by_key(|b| b.created_at);
+    }
+    groups
+}
+
+/// Removes the unpinned bullets `strategy` values least (oldest first on
+/// ties) until at most `max_bullets` remain. Pinned bullets are always kept,
+/// even if they alone exceed the limit.
+pub fn evict_bullets(
+    context: &ContextState,
+    max_bullets: usize,
+    strategy: EvictionStrategy,
+) -> (ContextState, Vec<String>) {
+    let now = Utc::now();
+    let excess = context.bullets.len().saturating_sub(max_bullets);
+    let mut candidates: Vec<(f64, &ContextBullet)> = context
+        .bullets
+        .values()
+        .filter(|b| !b.pinned)
+        .map(|b| (strategy.retention(b, now), b))
+        .collect();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.created_at.cmp(&b.1.created_at)));
+    let evicted: Vec<String> = candidates.into_iter().take(excess).map(|(_, b)| b.id.clone()).collect();
+    if evicted.is_empty() {
+        return (context.clone(), evicted);
+    }
+
+    let mut next = context.clone();
+    for id in &evicted {
+        next.bullets.remove(id);
+    }
+    next.version.increment();
+    (next, evicted)
+}
+
+/// Start of the period a relative date expression refers to: "yesterday",
+/// "this week", "last week", "last N days/weeks/months" or
+/// "since YYYY-MM-DD". Days start at midnight UTC; weeks start on Monday.
+pub fn parse_relative_date(expr: &str) -> Option<chrono::DateTime<Utc>> {
+    parse_relative_date_at(expr, Utc::now())
+}
+
+pub fn parse_relative_date_at(expr: &str, now: chrono::DateTime<Utc>) -> Option<chrono::DateTime<Utc>> {
+    use chrono::{Datelike, Duration, Months, NaiveDate};
+
+    let expr = expr.trim().to_lowercase();
+    let start_of_day = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|t| t.and_utc());
+    let today = now.date_naive();
+
+    match expr.as_str() {
+        "today" => return start_of_day(today),
+        "yesterday" => return start_of_day(today - Duration::days(1)),
+        "this week" => {
+            return start_of_day(today - Duration::days(today.weekday().num_days_from_monday() as i64));
+        }
+        _ => {}
+    }
+
+    if let Some(date) = expr.strip_prefix("since ") {
+        return NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok().and_then(start_of_day);
+    }
+
+    let rest = expr.strip_prefix("last ")?;
+    let (count, unit) = match rest.split_once(' ') {
+        Some((count, unit)) => (count.parse::<u32>().ok()?, unit),
+        None => (1, rest),
+    };
+    match unit.trim_end_matches('s') {
+        "day" => Some(now - Duration::days(count as i64)),
+        "week" => Some(now - Duration::weeks(count as i64)),
+        "month" => now.checked_sub_months(Months::new(count)),
+        _ => None,
+    }
+}
+
+pub fn bullets_created_since(
+    bullets: &HashMap<String, ContextBullet>,
+    since: chrono::DateTime<Utc>,
+) -> HashMap<String, ContextBullet> {
+    bullets
+        .iter()
+        .filter(|(_, bullet)| bullet.created_at >= since)
+        .map(|(id, bullet)| (id.clone(), bullet.clone()))
+        .collect()
+}
+
+const QUESTION_PREFIXES: &[&str] = &[
+    "what is ", "what are ", "what's ", "who is ", "who are ", "where is ", "define ", "tell me about ",
+];
+
+/// The noun phrase a definitional question asks about: "What is the borrow
+/// checker?" -> "borrow checker".
+pub fn query_subject(query: &str) -> Option<String> {
+    let query = query.trim().trim_end_matches('?').trim().to_lowercase();
+    let subject = QUESTION_PREFIXES.iter().find_map(|prefix| query.strip_prefix(prefix))?;
+    let subject = ["the ", "a ", "an "]
+        .iter()
+        .find_map(|article| subject.strip_prefix(article))
+        .unwrap_or(subject)
+        .trim();
+    (!subject.is_empty()).then(|| subject.to_string())
+}
+
+/// An answer stated outright in the bullets: the answer of a stored Q&A pair
+/// for the same question, or else a bullet with a sentence that starts with
+/// the question's subject ("Rust is ...").
+pub fn find_direct_answer(query: &str, bullets: &[ContextBullet]) -> Option<String> {
+    let question = tokenize_terms(query);
+    let stored_answer = bullets.iter().find_map(|bullet| {
+        let (q, a) = bullet.content.strip_prefix("Q:")?.split_once("\nA:")?;
+        (tokenize_terms(q) == question).then(|| a.trim().to_string())
+    });
+    if stored_answer.is_some() {
+        return stored_answer;
+    }
+
+    let subject = query_subject(query)?;
+    let states_subject = |sentence: &str| {
+        let sentence = sentence.trim().to_lowercase();
+        sentence
+            .strip_prefix(&subject)
+            .is_some_and(|rest| rest.starts_with(|c: char| !c.is_alphanumeric()))
+    };
+    bullets
+        .iter()
+        .find(|bullet| bullet.content.split(['.', '!', '?', '\n']).any(states_subject))
+        .map(|bullet| bullet.content.clone())
+}
+
+/// Lowercase hex SHA-256 of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture_bullet, fixture_context};
+    use proptest::prelude::*;
+
+    fn prompt_fixture() -> ContextState {
+        fixture_context(vec![
+            fixture_bullet("rustfmt-1", "Run cargo fmt before committing Rust code", &["rust"], 3, 0),
+            fixture_bullet("clippy-02", "Treat clippy warnings as errors in CI", &["rust", "ci"], 1, 1),
+            fixture_bullet(
+                "conv-0003",
+                "Q: How do I lint Rust code?\nA: Use cargo clippy.",
+                &["conversation"],
+                0,
+                2,
+            ),
+        ])
+    }
+
+    #[test]
+    fn context_prompt_snapshot() {
+        let context = prompt_fixture();
+        let mut bullets: Vec<ContextBullet> = context.bullets.values().cloned().collect();
+        bullets.sort_by(|a, b| a.id.cmp(&b.id));
+        insta::assert_snapshot!(build_context_prompt(&bullets, false));
+    }
+
+    #[test]
+    fn sha256_hex_matches_fips_vectors() {
+        assert_eq!(sha256_hex(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    fn node_votes(votes: &[(usize, u64)]) -> GCounter {
+        let mut counter = GCounter::default();
+        for (node, count) in votes {
+            *counter.counts.entry(format!("node-{}", node)).or_insert(0) += count;
+        }
+        counter
+    }
+
+    /// A replica's view of bullet `id`: a revision from `minute`, per-node
+    /// votes and some feedback predating them.
+    fn replica_bullet() -> impl Strategy<Value = ContextBullet> {
+        let template = fixture_bullet("b", "content", &[], 0, 0);
+        let votes = proptest::collection::vec((0..3usize, 0..4u64), 0..4);
+        (0..3usize, 0..3i64, 0..2usize, 0..2u32, votes, 0..3i32).prop_map(
+            move |(id, minute, content, revision, votes, legacy)| {
+                let mut bullet = ContextBullet {
+                    id: format!("b{}", id),
+                    content: format!("content {}", content),
+                    revision,
+                    created_at: template.created_at + chrono::Duration::minutes(minute),
+                    ..template.clone()
+                };
+                bullet.helpful_votes = node_votes(&votes);
+                bullet.harmful_votes = node_votes(&votes[..votes.len() / 2]);
+                bullet.helpful_count = bullet.helpful_votes.total() as i32 + legacy;
+                bullet.harmful_count = bullet.harmful_votes.total() as i32;
+                bullet
+            },
+        )
+    }
+
+    fn replica() -> impl Strategy<Value = ContextState> {
+        (proptest::collection::vec(replica_bullet(), 0..5), proptest::collection::vec((0..3usize, 0..4u64), 0..3))
+            .prop_map(|(bullets, clock)| ContextState {
+                version: node_votes(&clock),
+                ..fixture_context(bullets)
+            })
+    }
+
+    fn state_json(context: &ContextState) -> serde_json::Value {
+        serde_json::to_value(context).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn crdt_merge_is_commutative(a in replica(), b in replica()) {
+            prop_assert_eq!(state_json(&crdt_merge(&a, &b)), state_json(&crdt_merge(&b, &a)));
+        }
+
+        #[test]
+        fn crdt_merge_is_idempotent(a in replica(), b in replica()) {
+            prop_assert_eq!(state_json(&crdt_merge(&a, &a)), state_json(&a));
+            let merged = crdt_merge(&a, &b);
+            prop_assert_eq!(state_json(&crdt_merge(&merged, &b)), state_json(&merged));
+        }
+    }
+
+    #[test]
+    fn crdt_merge_sums_votes_from_different_nodes() {
+        let base = fixture_bullet("shared", "Run cargo fmt", &[], 0, 0);
+        let mut ours = base.clone();
+        ours.helpful_votes = node_votes(&[(1, 2)]);
+        ours.helpful_count = 2;
+        let mut theirs = base;
+        theirs.helpful_votes = node_votes(&[(2, 1)]);
+        theirs.helpful_count = 1;
+
+        let merged = crdt_merge(&fixture_context(vec![ours]), &fixture_context(vec![theirs]));
+        let merged = crdt_merge(&merged, &merged);
+
+        assert_eq!(merged.bullets["shared"].helpful_count, 3);
+    }
+
+    #[test]
+    fn plan_sync_fetches_remote_edits() {
+        let local = fixture_bullet("shared", "Run cargo fmt", &[], 2, 0);
+        let edited = edit_bullet_content(&local, "Run cargo fmt --check in CI", 5);
+        let rolled_back = rollback_bullet_content(&edited, 1).unwrap();
+        let manifest = |bullet: &ContextBullet| compute_sync_manifest(&fixture_context(vec![bullet.clone()]));
+
+        let plan = plan_sync(&manifest(&local), &manifest(&edited));
+        assert_eq!(plan.fetch, vec!["shared".to_string()]);
+        assert_eq!(plan.conflicts, 0);
+
+        let plan = plan_sync(&manifest(&edited), &manifest(&rolled_back));
+        assert_eq!(plan.fetch, vec!["shared".to_string()]);
+        assert_eq!(plan.conflicts, 0);
+    }
+}
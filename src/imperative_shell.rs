@@ -1,42 +1,281 @@
 // ACE Imperative Shell - Side Effects Layer
 #![allow(dead_code)]
+use crate::functional_core::{
+    dry_run_response, extract_json, parse_model_info, sha256_hex, parse_pull_progress, render_plugin_script, validate_aliases,
+    validate_command_plugins, validate_json,
+};
 use crate::types::*;
-use futures::stream::StreamExt;
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde_json::json;
+use std::sync::{OnceLock, RwLock};
+
+pub type TextStream = BoxStream<'static, Result<String>>;
+pub type PullStream = BoxStream<'static, PullProgress>;
+
+pub const MODEL_NOT_FOUND: &str = "Model not found";
+
+// Abstraction over the text-generation service so components can be wired
+// to something other than a live Ollama server.
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    async fn initialize(&self) -> Result<bool>;
+
+    async fn generate_with_options(&self, prompt: &str, options: &GenerationOptions) -> Result<String>;
+
+    async fn generate_stream_with_options(
+        &self,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<TextStream>;
+
+    async fn model_info(&self) -> Result<ModelInfo> {
+        Err("Model info not supported by this backend".to_string())
+    }
+
+    fn set_context_window(&self, _context_window: i32) {}
+
+    fn set_model(&self, _model: &str) {}
+
+    async fn pull_model(&self, _model: &str) -> Result<PullStream> {
+        Err("Model pulling not supported by this backend".to_string())
+    }
+
+    async fn generate_multimodal(&self, _prompt: &MultimodalPrompt) -> Result<String> {
+        Err("Image input not supported by this backend".to_string())
+    }
+
+    /// Capabilities of the current model, if they have been probed.
+    fn capabilities(&self) -> Option<ModelCapabilities> {
+        None
+    }
+
+    async fn generate(&self, prompt: &str) -> Result<String> {
+        self.generate_with_options(prompt, &GenerationOptions::default()).await
+    }
+
+    async fn generate_with_thinking(&self, prompt: &str, enable_thinking: bool) -> Result<String> {
+        let options = GenerationOptions {
+            enable_thinking,
+            ..Default::default()
+        };
+        self.generate_with_options(prompt, &options).await
+    }
+
+    async fn generate_stream(&self, prompt: &str) -> Result<TextStream> {
+        self.generate_stream_with_options(prompt, &GenerationOptions::default()).await
+    }
+
+    async fn generate_stream_with_thinking(
+        &self,
+        prompt: &str,
+        enable_thinking: bool,
+    ) -> Result<TextStream> {
+        let options = GenerationOptions {
+            enable_thinking,
+            ..Default::default()
+        };
+        self.generate_stream_with_options(prompt, &options).await
+    }
+}
+
+/// Generates a JSON response and deserializes it into `T`. When `schema` is
+/// given the parsed value must also satisfy it; a stricter schema rejects more
+/// malformed output but means more failed calls with an unreliable model.
+pub async fn generate_structured<T: DeserializeOwned>(
+    client: &dyn LlmBackend,
+    prompt: &str,
+    schema: Option<&serde_json::Value>,
+) -> Result<T> {
+    if client.capabilities().is_some_and(|c| !c.supports_structured_output) {
+        return Err("Model does not support structured output".to_string());
+    }
+
+    let prompt = format!("{}\n\nRespond with valid JSON only.", prompt);
+    let response = client.generate(&prompt).await?;
+
+    let value = extract_json(&response)
+        .ok_or_else(|| "Parse error: response contains no JSON".to_string())?;
+    if let Some(schema) = schema {
+        validate_json(&value, schema).map_err(|e| format!("Parse error: {}", e))?;
+    }
+    serde_json::from_value(value).map_err(|e| format!("Parse error: {}", e))
+}
+
+/// Polls `client.initialize()` until it succeeds, `max_attempts` runs out or
+/// `timeout` elapses; the last backend error is returned on failure.
+pub async fn initialize_with_retry(client: &dyn LlmBackend, init: &InitConfig) -> Result<bool> {
+    let started = std::time::Instant::now();
+    let max_attempts = init.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match client.initialize().await {
+            Ok(ready) => return Ok(ready),
+            Err(e) if attempt >= max_attempts || started.elapsed() + init.poll_interval > init.timeout => {
+                return Err(e);
+            }
+            Err(e) => {
+                log_debug(&format!("Backend not ready: {}", e));
+                log_info(&format!("Waiting for Ollama... (attempt {}/{})", attempt, max_attempts));
+                tokio::time::sleep(init.poll_interval).await;
+                attempt += 1;
+            }
+        }
+    }
+}
 
 pub struct OllamaClient {
-    config: OllamaConfig,
-    client: Client,
+    config: RwLock<OllamaConfig>,
+    capabilities: RwLock<Option<ModelCapabilities>>,
+    // Built on first request so offline runs never open a connection pool.
+    client: OnceLock<Client>,
 }
 
 impl OllamaClient {
     pub fn new(config: OllamaConfig) -> Self {
         Self {
-            config,
-            client: Client::new(),
+            config: RwLock::new(config),
+            capabilities: RwLock::new(None),
+            client: OnceLock::new(),
+        }
+    }
+
+    fn client(&self) -> &Client {
+        self.client.get_or_init(Client::new)
+    }
+
+    pub fn config(&self) -> OllamaConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    fn thinking_enabled(&self, requested: bool) -> bool {
+        match *self.capabilities.read().unwrap() {
+            Some(c) if requested && !c.supports_thinking => {
+                log_warning("Model does not support thinking; generating without it");
+                false
+            }
+            _ => requested,
+        }
+    }
+
+    pub async fn get_model_info(&self, model: &str) -> Result<ModelInfo> {
+        let url = format!("{}/api/show", self.config().url);
+        let resp = self
+            .client()
+            .post(&url)
+            .json(&json!({ "name": model }))
+            .send()
+            .await
+            .map_err(|e| format!("Connection failed: {}", e))?;
+
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(format!("{}: {}", MODEL_NOT_FOUND, model));
+        }
+        if !resp.status().is_success() {
+            return Err(format!("API error: {}", resp.status()));
         }
+
+        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+        let info = parse_model_info(&json)
+            .ok_or_else(|| format!("Parse error: no context length reported for {}", model))?;
+        if model == self.config().model {
+            *self.capabilities.write().unwrap() = Some(info.capabilities);
+        }
+        Ok(info)
+    }
+
+    /// One embedding per input text from `/api/embed` (Ollama 0.5+), using
+    /// the configured `embed_model`.
+    pub async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let config = self.config();
+        let model = config
+            .embed_model
+            .ok_or_else(|| "No embed_model configured".to_string())?;
+        let url = format!("{}/api/embed", config.url);
+        let resp = self
+            .client()
+            .post(&url)
+            .json(&json!({ "model": model, "input": texts }))
+            .send()
+            .await
+            .map_err(|e| format!("Connection failed: {}", e))?;
+        // Servers older than 0.5 answer 404 as well, having no /api/embed
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(format!("{}: {} (or Ollama is older than 0.5)", MODEL_NOT_FOUND, model));
+        }
+        if !resp.status().is_success() {
+            return Err(format!("API error: {}", resp.status()));
+        }
+
+        #[derive(serde::Deserialize)]
+        struct EmbedResponse {
+            embeddings: Vec<Vec<f32>>,
+        }
+        let body: EmbedResponse = resp.json().await.map_err(|e| format!("Parse error: {}", e))?;
+        if body.embeddings.len() != texts.len() {
+            return Err(format!(
+                "Parse error: expected {} embeddings, got {}",
+                texts.len(),
+                body.embeddings.len()
+            ));
+        }
+        Ok(body.embeddings)
     }
 
-    pub async fn initialize(&self) -> Result<bool> {
-        let url = format!("{}/api/tags", self.config.url);
-        match self.client.get(&url).send().await {
+    /// Names of the models the Ollama server has pulled.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let url = format!("{}/api/tags", self.config().url);
+        let resp = self
+            .client()
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Connection failed: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("API error: {}", resp.status()));
+        }
+        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+        Ok(json["models"]
+            .as_array()
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m["name"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OllamaClient {
+    async fn initialize(&self) -> Result<bool> {
+        let config = self.config();
+        if config.dry_run {
+            return Ok(true);
+        }
+        let url = format!("{}/api/tags", config.url);
+        match self.client().get(&url).send().await {
             Ok(resp) if resp.status().is_success() => Ok(true),
             Ok(resp) => Err(format!("Ollama not available: {}", resp.status())),
             Err(e) => Err(format!("Connection failed: {}", e)),
         }
     }
 
-    pub async fn generate(&self, prompt: &str) -> Result<String> {
-        self.generate_with_thinking(prompt, false).await
-    }
-
-    pub async fn generate_with_thinking(&self, prompt: &str, enable_thinking: bool) -> Result<String> {
-        let url = format!("{}/api/generate", self.config.url);
+    async fn generate_with_options(&self, prompt: &str, options: &GenerationOptions) -> Result<String> {
+        let config = self.config();
+        if config.dry_run {
+            return Ok(dry_run_response(prompt));
+        }
+        let enable_thinking = self.thinking_enabled(options.enable_thinking);
+        let url = format!("{}/api/generate", config.url);
+        let temperature = options.temperature.unwrap_or(config.temperature);
         let mut options = json!({
-            "temperature": self.config.temperature,
-            "num_predict": self.config.max_tokens,
-            "num_ctx": self.config.context_window
+            "temperature": temperature,
+            "num_predict": config.max_tokens,
+            "num_ctx": config.context_window
         });
         
         if enable_thinking {
@@ -44,7 +283,7 @@ impl OllamaClient {
         }
         
         let payload = json!({
-            "model": self.config.model,
+            "model": config.model,
             "prompt": prompt,
             "stream": false,
             "options": options
@@ -56,7 +295,7 @@ impl OllamaClient {
             std::time::Duration::from_secs(120) 
         };
 
-        match self.client.post(&url).json(&payload).timeout(timeout).send().await {
+        match self.client().post(&url).json(&payload).timeout(timeout).send().await {
             Ok(resp) if resp.status().is_success() => {
                 let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
                 Ok(json["response"].as_str().unwrap_or("").trim().to_string())
@@ -66,23 +305,59 @@ impl OllamaClient {
         }
     }
 
-    pub async fn generate_stream(
-        &self,
-        prompt: &str,
-    ) -> Result<impl futures::Stream<Item = Result<String>>> {
-        self.generate_stream_with_thinking(prompt, false).await
+    /// Sends the images alongside the prompt; needs a vision model such as llava.
+    async fn generate_multimodal(&self, prompt: &MultimodalPrompt) -> Result<String> {
+        let config = self.config();
+        if config.dry_run {
+            return Ok(dry_run_response(&prompt.text));
+        }
+        let url = format!("{}/api/generate", config.url);
+        let images: Vec<&str> = prompt.images.iter().map(|image| image.data.as_str()).collect();
+        let payload = json!({
+            "model": config.model,
+            "prompt": prompt.text,
+            "images": images,
+            "stream": false,
+            "options": {
+                "temperature": config.temperature,
+                "num_predict": config.max_tokens,
+                "num_ctx": config.context_window
+            }
+        });
+
+        match self
+            .client()
+            .post(&url)
+            .json(&payload)
+            .timeout(std::time::Duration::from_secs(300))
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => {
+                let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+                Ok(json["response"].as_str().unwrap_or("").trim().to_string())
+            }
+            Ok(resp) => Err(format!("API error: {}", resp.status())),
+            Err(e) => Err(format!("Generation failed: {}", e)),
+        }
     }
 
-    pub async fn generate_stream_with_thinking(
+    async fn generate_stream_with_options(
         &self,
         prompt: &str,
-        enable_thinking: bool,
-    ) -> Result<impl futures::Stream<Item = Result<String>>> {
-        let url = format!("{}/api/generate", self.config.url);
+        options: &GenerationOptions,
+    ) -> Result<TextStream> {
+        let config = self.config();
+        if config.dry_run {
+            return Ok(futures::stream::once(futures::future::ready(Ok(dry_run_response(prompt)))).boxed());
+        }
+        let enable_thinking = self.thinking_enabled(options.enable_thinking);
+        let url = format!("{}/api/generate", config.url);
+        let temperature = options.temperature.unwrap_or(config.temperature);
         let mut options = json!({
-            "temperature": self.config.temperature,
-            "num_predict": self.config.max_tokens,
-            "num_ctx": self.config.context_window
+            "temperature": temperature,
+            "num_predict": config.max_tokens,
+            "num_ctx": config.context_window
         });
         
         if enable_thinking {
@@ -90,7 +365,7 @@ impl OllamaClient {
         }
         
         let payload = json!({
-            "model": self.config.model,
+            "model": config.model,
             "prompt": prompt,
             "stream": true,
             "options": options
@@ -103,7 +378,7 @@ impl OllamaClient {
         };
 
         let resp = self
-            .client
+            .client()
             .post(&url)
             .json(&payload)
             .timeout(timeout)
@@ -130,19 +405,519 @@ impl OllamaClient {
             Err(e) => Err(e.to_string()),
         });
 
-        Ok(stream)
+        Ok(stream.boxed())
+    }
+
+    async fn model_info(&self) -> Result<ModelInfo> {
+        if self.config().dry_run {
+            return Err("Model info not available in dry-run mode".to_string());
+        }
+        self.get_model_info(&self.config().model).await
+    }
+
+    fn set_context_window(&self, context_window: i32) {
+        self.config.write().unwrap().context_window = context_window;
+    }
+
+    fn set_model(&self, model: &str) {
+        self.config.write().unwrap().model = model.to_string();
+        *self.capabilities.write().unwrap() = None;
+    }
+
+    fn capabilities(&self) -> Option<ModelCapabilities> {
+        *self.capabilities.read().unwrap()
+    }
+
+    async fn pull_model(&self, model: &str) -> Result<PullStream> {
+        let url = format!("{}/api/pull", self.config().url);
+        let resp = self
+            .client()
+            .post(&url)
+            .json(&json!({ "name": model, "stream": true }))
+            .send()
+            .await
+            .map_err(|e| format!("Connection failed: {}", e))?;
+
+        if !resp.status().is_success() {
+            return Err(format!("API error: {}", resp.status()));
+        }
+
+        let stream = resp.bytes_stream().filter_map(|result| async move {
+            match result {
+                Ok(bytes) => String::from_utf8_lossy(&bytes)
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+                    .next_back()
+                    .map(|json| parse_pull_progress(&json)),
+                Err(e) => Some(PullProgress {
+                    status: format!("error: {}", e),
+                    percent: 0.0,
+                }),
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+}
+
+pub fn save_recordings(path: &std::path::Path, recordings: &[Recording]) -> Result<()> {
+    let mut lines = String::new();
+    for recording in recordings {
+        lines.push_str(&serde_json::to_string(recording).map_err(|e| e.to_string())?);
+        lines.push('\n');
+    }
+    std::fs::write(path, lines).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+/// One serialized `ContextBullet` per line.
+pub fn load_bullets_jsonl(path: &std::path::Path) -> Result<Vec<ContextBullet>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e)))
+        .collect()
+}
+
+pub const EMERGENCY_DUMP_FILE: &str = "ace_emergency_dump.json";
+
+static EMERGENCY_DUMP_ENABLED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+static EMERGENCY_SNAPSHOT: std::sync::Mutex<Option<ContextState>> = std::sync::Mutex::new(None);
+
+/// Installs a panic hook that writes the latest context snapshot to `path`
+/// before the default panic message is printed.
+///
+/// Best effort only: the dump serializes and allocates, so it can fail when
+/// the panic came from memory exhaustion (a true OOM aborts without running
+/// panic hooks at all), and it is skipped if the snapshot is locked.
+pub fn install_emergency_dump(path: &'static str) {
+    EMERGENCY_DUMP_ENABLED.store(true, std::sync::atomic::Ordering::Relaxed);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Ok(snapshot) = EMERGENCY_SNAPSHOT.try_lock() {
+            if let Some(context) = snapshot.as_ref() {
+                if let Ok(file) = std::fs::File::create(path) {
+                    let _ = serde_json::to_writer(file, context);
+                    eprintln!("Context saved to {}", path);
+                }
+            }
+        }
+        default_hook(info);
+    }));
+}
+
+/// Keeps the context the panic hook would dump; a no-op until
+/// `install_emergency_dump` is called.
+pub fn record_emergency_snapshot(context: &ContextState) {
+    if !EMERGENCY_DUMP_ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut snapshot) = EMERGENCY_SNAPSHOT.lock() {
+        *snapshot = Some(context.clone());
+    }
+}
+
+pub const SEARCH_ANALYTICS_FILE: &str = "analytics.jsonl";
+
+/// Appends search events to a JSONL log, creating it if needed.
+pub fn append_search_events(path: &std::path::Path, events: &[SearchEvent]) -> Result<()> {
+    use std::io::Write;
+    let mut lines = String::new();
+    for event in events {
+        lines.push_str(&serde_json::to_string(event).map_err(|e| e.to_string())?);
+        lines.push('\n');
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+pub fn load_recordings(path: &std::path::Path) -> Result<Vec<Recording>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| format!("Parse error: {}", e)))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    pub tokens: f64,
+    pub last_refill: std::time::Instant,
+}
+
+/// Token-bucket limiter on estimated prompt tokens sent to the LLM.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    pub token_bucket: TokenBucket,
+    pub capacity: u32,
+    /// Tokens restored per second.
+    pub refill_rate: f64,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, refill_rate: f64) -> Self {
+        Self {
+            token_bucket: TokenBucket {
+                tokens: capacity as f64,
+                last_refill: std::time::Instant::now(),
+            },
+            capacity,
+            refill_rate,
+        }
+    }
+
+    /// Takes `cost` tokens, or returns how long until enough have refilled.
+    /// Costs above capacity are clamped so a single large call can still run.
+    pub fn try_acquire(&mut self, cost: usize) -> std::result::Result<(), std::time::Duration> {
+        let now = std::time::Instant::now();
+        let elapsed = now.duration_since(self.token_bucket.last_refill).as_secs_f64();
+        self.token_bucket.tokens = (self.token_bucket.tokens + elapsed * self.refill_rate).min(self.capacity as f64);
+        self.token_bucket.last_refill = now;
+
+        let cost = (cost as f64).min(self.capacity as f64);
+        if self.token_bucket.tokens >= cost {
+            self.token_bucket.tokens -= cost;
+            Ok(())
+        } else if self.refill_rate > 0.0 {
+            let missing = cost - self.token_bucket.tokens;
+            Err(std::time::Duration::from_secs_f64(missing / self.refill_rate))
+        } else {
+            Err(std::time::Duration::MAX)
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(20_000, 200.0)
+    }
+}
+
+pub const APP_CONFIG_FILE: &str = "ace.toml";
+
+/// Reads `ace.toml`; a missing file means all defaults.
+pub fn load_app_config(path: &std::path::Path) -> Result<AppConfig> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => {
+            let mut config: AppConfig =
+                toml::from_str(&text).map_err(|e| format!("Config error in {}: {}", path.display(), e))?;
+            config.interactive.aliases = std::mem::take(&mut config.interactive.aliases)
+                .into_iter()
+                .map(|(alias, command)| (alias.trim_start_matches('/').to_string(), command))
+                .collect();
+            // User aliases override the built-ins but don't remove them
+            for (alias, command) in InteractiveConfig::default_aliases() {
+                config.interactive.aliases.entry(alias).or_insert(command);
+            }
+            validate_aliases(&config.interactive.aliases)?;
+            validate_command_plugins(&config.commands)?;
+            Ok(config)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AppConfig::default()),
+        Err(e) => Err(format!("Cannot read {}: {}", path.display(), e)),
+    }
+}
+
+/// Printed while telemetry is compiled in and the user hasn't configured it.
+pub fn print_telemetry_notice(config: &AppConfig) {
+    if cfg!(feature = "telemetry") && config.telemetry.is_none() {
+        log_info("ACE collects metrics locally. Set `telemetry.local_metrics = false` in ace.toml to opt out.");
+    }
+}
+
+pub const CONFIG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// A backend for a new model, already initialized and ready to swap in.
+pub struct BackendSwap {
+    pub model: String,
+    pub backend: std::sync::Arc<dyn LlmBackend>,
+}
+
+/// Polls the modification time of `path` in the background. When the file's
+/// `model` changes, an `OllamaClient` for it is built and initialized off the
+/// request path and sent on the returned channel. Models that fail to
+/// initialize are logged and skipped. Stops once the receiver is dropped.
+pub fn watch_config_model(path: std::path::PathBuf, config: OllamaConfig) -> tokio::sync::mpsc::Receiver<BackendSwap> {
+    let (swaps, receiver) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(async move {
+        let modified = |path: &std::path::Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut last_modified = modified(&path);
+        let mut current = config.model.clone();
+        let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if swaps.is_closed() {
+                break;
+            }
+            let now = modified(&path);
+            if now == last_modified {
+                continue;
+            }
+            last_modified = now;
+            let Some(model) = load_app_config(&path).ok().and_then(|c| c.model) else {
+                continue;
+            };
+            if model == current {
+                continue;
+            }
+
+            let client = OllamaClient::new(OllamaConfig {
+                model: model.clone(),
+                ..config.clone()
+            });
+            if let Err(e) = client.initialize().await {
+                log_warning(&format!("Not switching to {}: {}", model, e));
+                continue;
+            }
+            current = model.clone();
+            let swap = BackendSwap {
+                model,
+                backend: std::sync::Arc::new(client),
+            };
+            if swaps.send(swap).await.is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+pub const COMMAND_PLUGIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Runs a plugin's script through `sh -c` and returns its stdout (plus
+/// stderr, if any). The process is killed after `COMMAND_PLUGIN_TIMEOUT`.
+pub async fn run_command_plugin(plugin: &CommandPlugin, query: &str, session_id: &str) -> Result<String> {
+    let script = render_plugin_script(&plugin.script, query, session_id);
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&script)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(COMMAND_PLUGIN_TIMEOUT, child)
+        .await
+        .map_err(|_| format!("/{} timed out after {}s", plugin.name, COMMAND_PLUGIN_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("/{} failed to start: {}", plugin.name, e))?;
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        text.push_str(&stderr);
+    }
+    if output.status.success() {
+        Ok(text)
+    } else {
+        Err(format!("/{} exited with {}: {}", plugin.name, output.status, text.trim()))
     }
 }
 
 // Logging functions
+struct LogSink {
+    format: LogFormat,
+    file: Option<std::sync::Mutex<std::fs::File>>,
+}
+
+static LOG_SINK: OnceLock<LogSink> = OnceLock::new();
+
+/// Sets the process-wide log format and optional log file (appended to, in
+/// the same format). Only the first call has any effect.
+pub fn init_logging(format: LogFormat, log_file: Option<&std::path::Path>) -> Result<()> {
+    let file = match log_file {
+        Some(path) => Some(std::sync::Mutex::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Cannot open log file {}: {}", path.display(), e))?,
+        )),
+        None => None,
+    };
+    let _ = LOG_SINK.set(LogSink { format, file });
+    Ok(())
+}
+
+fn log_line(format: LogFormat, level: &str, message: &str) -> String {
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    match format {
+        LogFormat::Text => format!("{} {:<7} {}", timestamp, level.to_uppercase(), message),
+        LogFormat::Json => json!({
+            "timestamp": timestamp,
+            "level": level,
+            "target": env!("CARGO_PKG_NAME"),
+            "message": message,
+        })
+        .to_string(),
+    }
+}
+
+// Text goes to stdout with an icon; JSON lines go to stderr so they can be
+// collected apart from the conversation.
+fn emit(level: &str, key: IconKey, message: &str) {
+    use std::io::Write;
+    let sink = LOG_SINK.get();
+    let format = sink.map(|sink| sink.format).unwrap_or_default();
+    match format {
+        LogFormat::Text => println!("{} {}", icon(key), message),
+        LogFormat::Json => eprintln!("{}", log_line(format, level, message)),
+    }
+    if let Some(file) = sink.and_then(|sink| sink.file.as_ref()) {
+        if let Ok(mut file) = file.lock() {
+            let _ = writeln!(file, "{}", log_line(format, level, message));
+        }
+    }
+}
+
 pub fn log_info(message: &str) {
-    println!("ℹ️  {}", message);
+    emit("info", IconKey::Info, message);
 }
 
 pub fn log_success(message: &str) {
-    println!("✅ {}", message);
+    emit("info", IconKey::Success, message);
 }
 
 pub fn log_error(message: &str) {
-    println!("❌ {}", message);
+    emit("error", IconKey::Error, message);
+}
+
+pub fn log_warning(message: &str) {
+    emit("warn", IconKey::Warning, message);
+}
+
+pub fn log_debug(message: &str) {
+    if std::env::var_os("ACE_DEBUG").is_some() {
+        emit("debug", IconKey::Debug, message);
+    }
+}
+
+/// `GET {remote_url}/sync/manifest`
+pub async fn fetch_sync_manifest(remote_url: &str) -> Result<SyncManifest> {
+    let url = format!("{}/sync/manifest", remote_url.trim_end_matches('/'));
+    let resp = reqwest::get(&url).await.map_err(|e| format!("Sync request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Sync manifest unavailable: {}", resp.status()));
+    }
+    resp.json().await.map_err(|e| format!("Invalid sync manifest: {}", e))
+}
+
+/// Identifies a manifest's contents regardless of entry order.
+pub fn sync_manifest_hash(manifest: &SyncManifest) -> String {
+    let mut entries: Vec<(&str, u64, i32)> = manifest
+        .entries
+        .iter()
+        .map(|e| (e.id.as_str(), e.content_hash, e.version))
+        .collect();
+    entries.sort_unstable();
+    sha256_hex(serde_json::to_string(&entries).unwrap_or_default().as_bytes())
+}
+
+/// Polls a sync remote's manifest with exponential back-off: every quiet
+/// cycle multiplies the wait by `backoff_factor` up to `max_interval`, and a
+/// change resets it to `interval`.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncPoller {
+    pub interval: std::time::Duration,
+    pub max_interval: std::time::Duration,
+    pub backoff_factor: f64,
+}
+
+impl Default for SyncPoller {
+    fn default() -> Self {
+        Self {
+            interval: std::time::Duration::from_secs(5),
+            max_interval: std::time::Duration::from_secs(300),
+            backoff_factor: 2.0,
+        }
+    }
+}
+
+impl SyncPoller {
+    pub fn next_interval(&self, current: std::time::Duration, changed: bool) -> std::time::Duration {
+        if changed {
+            self.interval
+        } else {
+            current.mul_f64(self.backoff_factor.max(1.0)).min(self.max_interval)
+        }
+    }
+
+    /// Sends the remote manifest whenever its hash changes, the first poll
+    /// included, and POSTs to `webhook` if one is set. Unreachable remotes
+    /// count as quiet cycles. Stops once the receiver is dropped.
+    pub fn spawn(self, remote_url: String, webhook: Option<String>) -> tokio::sync::mpsc::Receiver<SyncManifest> {
+        let (changes, receiver) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut last_hash = None;
+            let mut wait = self.interval;
+            loop {
+                let changed = match fetch_sync_manifest(&remote_url).await {
+                    Ok(manifest) => {
+                        let hash = sync_manifest_hash(&manifest);
+                        let changed = last_hash.as_ref() != Some(&hash);
+                        if changed {
+                            last_hash = Some(hash);
+                            if let Some(webhook) = &webhook {
+                                notify_sync_webhook(webhook, &remote_url, &manifest).await;
+                            }
+                            if changes.send(manifest).await.is_err() {
+                                break;
+                            }
+                        }
+                        changed
+                    }
+                    Err(e) => {
+                        log_debug(&format!("Sync poll of {} failed: {}", remote_url, e));
+                        false
+                    }
+                };
+                wait = self.next_interval(wait, changed);
+                tokio::time::sleep(wait).await;
+                if changes.is_closed() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
+
+async fn notify_sync_webhook(webhook: &str, remote_url: &str, manifest: &SyncManifest) {
+    let payload = json!({
+        "event": "sync_changed",
+        "remote": remote_url,
+        "entries": manifest.entries.len(),
+    });
+    let sent = Client::new()
+        .post(webhook)
+        .json(&payload)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await;
+    match sent {
+        Ok(resp) if resp.status().is_success() => {}
+        Ok(resp) => log_warning(&format!("Sync webhook returned {}", resp.status())),
+        Err(e) => log_warning(&format!("Sync webhook failed: {}", e)),
+    }
+}
+
+/// `GET {remote_url}/sync/bullets?ids[]=...`
+pub async fn fetch_sync_bullets(remote_url: &str, ids: &[String]) -> Result<Vec<ContextBullet>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let query = ids
+        .iter()
+        .map(|id| format!("ids[]={}", urlencoding::encode(id)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let url = format!("{}/sync/bullets?{}", remote_url.trim_end_matches('/'), query);
+    let resp = reqwest::get(&url).await.map_err(|e| format!("Sync request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("Sync bullets unavailable: {}", resp.status()));
+    }
+    resp.json().await.map_err(|e| format!("Invalid sync bullets: {}", e))
 }
@@ -1,27 +1,54 @@
 // ACE System - Main Entry Point
 mod ace;
+#[cfg(feature = "embedding")]
+mod embedding;
 mod functional_core;
 mod imperative_shell;
+mod obsidian;
+mod pipeline;
+mod plugins;
+mod secrets;
+#[cfg(any(test, feature = "test-utils"))]
+mod testing;
 mod tools;
+mod transcript;
 mod types;
 
-use ace::ACEFramework;
-use tools::{SearchTool, ThinkingTool, DeepResearchTool};
+use ace::{ACEFramework, ContextStore};
+use tools::{ResearchDepth, ResearchProgressEvent, ResearchTemplate, SearchTool};
+use colored::Colorize;
+use functional_core::{
+    bullets_to_anki_csv, diff_strings, estimate_tokens, expand_alias, match_command_plugin, parse_relative_date,
+    plugin_args_match,
+};
 use futures::StreamExt;
-use imperative_shell::{log_error, log_info, log_success};
+use imperative_shell::{
+    append_search_events, init_logging, install_emergency_dump, load_app_config, log_error, log_info, log_success,
+    log_warning, print_telemetry_notice, run_command_plugin, LlmBackend, OllamaClient, RateLimiter, SyncPoller,
+    APP_CONFIG_FILE, EMERGENCY_DUMP_FILE, SEARCH_ANALYTICS_FILE,
+};
+use obsidian::import_obsidian_vault;
+use pipeline::AcePipeline;
+use plugins::PluginStore;
+use secrets::{resolve_api_key, SecretStore};
 use std::io::{self, Write};
-use types::OllamaConfig;
+use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use types::{
+    icon, set_display_config, AppConfig, CommandPlugin, ContextBullet, DiffToken, DisplayConfig, IconKey, InitConfig,
+    OllamaConfig,
+};
 
 async fn demo_mode(ace: &mut ACEFramework) {
     log_info("ACE Demo Mode - Testing All Features");
     println!("\n{}", "=".repeat(60));
 
     // 1. Basic ACE Query
-    println!("\n🧪 Test 1: Basic ACE Query");
+    println!("\n{} Test 1: Basic ACE Query", icon(IconKey::Test));
     println!("{}", "-".repeat(60));
     let query = "What is Agentic Context Engineering?";
     println!("Query: {}", query);
-    print!("\n🤖 Response:\n");
+    print!("\n{} Response:\n", icon(IconKey::Robot));
     io::stdout().flush().unwrap();
     
     match ace.process_query_stream(query).await {
@@ -35,20 +62,22 @@ async fn demo_mode(ace: &mut ACEFramework) {
                 }
             }
             println!();
-            ace.learn_from_interaction(query, &full_response).await;
+            if let Err(e) = ace.learn_from_interaction(query, &full_response).await {
+                log_error(&e);
+            }
         }
         Err(e) => log_error(&format!("Error: {}", e)),
     }
     let stats = ace.get_context_stats();
-    println!("📈 Context: {} bullets learned", stats.total_bullets);
+    println!("{} Context: {} bullets learned", icon(IconKey::Growth), stats.total_bullets);
 
     // 2. Context Learning
     println!("\n{}", "=".repeat(60));
-    println!("\n🧪 Test 2: Context Learning");
+    println!("\n{} Test 2: Context Learning", icon(IconKey::Test));
     println!("{}", "-".repeat(60));
     let query = "Write a Rust function to calculate factorial";
     println!("Query: {}", query);
-    print!("\n🤖 Response:\n");
+    print!("\n{} Response:\n", icon(IconKey::Robot));
     io::stdout().flush().unwrap();
     
     match ace.process_query_stream(query).await {
@@ -62,21 +91,23 @@ async fn demo_mode(ace: &mut ACEFramework) {
                 }
             }
             println!();
-            ace.learn_from_interaction(query, &full_response).await;
+            if let Err(e) = ace.learn_from_interaction(query, &full_response).await {
+                log_error(&e);
+            }
         }
         Err(e) => log_error(&format!("Error: {}", e)),
     }
     let stats = ace.get_context_stats();
-    println!("📈 Context: {} bullets learned", stats.total_bullets);
+    println!("{} Context: {} bullets learned", icon(IconKey::Growth), stats.total_bullets);
 
     // 3. Search in Context
     println!("\n{}", "=".repeat(60));
-    println!("\n🧪 Test 3: Search in Context");
+    println!("\n{} Test 3: Search in Context", icon(IconKey::Test));
     println!("{}", "-".repeat(60));
     let search_tool = SearchTool::new(false);
     let context = ace.curator.get_context();
-    let results = search_tool.search_context("Rust", &context.bullets);
-    println!("🔍 Search 'Rust': Found {} results", results.len());
+    let results = search_tool.search_context("Rust", &context.bullets, None);
+    println!("{} Search 'Rust': Found {} results", icon(IconKey::Search), results.len());
     for (i, r) in results.iter().take(2).enumerate() {
         let preview: String = r.content.chars().take(60).collect();
         println!("  {}. {}...", i + 1, preview);
@@ -84,11 +115,11 @@ async fn demo_mode(ace: &mut ACEFramework) {
 
     // 4. Thinking Mode
     println!("\n{}", "=".repeat(60));
-    println!("\n🧪 Test 4: Deep Thinking");
+    println!("\n{} Test 4: Deep Thinking", icon(IconKey::Test));
     println!("{}", "-".repeat(60));
     let query = "Compare functional vs OOP";
     println!("Query: {}", query);
-    println!("\n🧠 Thinking:");
+    println!("\n{} Thinking:", icon(IconKey::Thinking));
     match ace.think(query).await {
         Ok(response) => {
             let preview: String = response.chars().take(200).collect();
@@ -99,27 +130,28 @@ async fn demo_mode(ace: &mut ACEFramework) {
 
     // 5. Web Search
     println!("\n{}", "=".repeat(60));
-    println!("\n🧪 Test 5: Web Search");
+    println!("\n{} Test 5: Web Search", icon(IconKey::Test));
     println!("{}", "-".repeat(60));
     let search_tool_web = SearchTool::new(true);
-    println!("🔍 Searching 'Rust programming'...");
+    println!("{} Searching 'Rust programming'...", icon(IconKey::Search));
     let web_results = search_tool_web.search("Rust programming", &context.bullets).await;
     println!("Found {} results (context + web)", web_results.len());
     for (i, r) in web_results.iter().take(2).enumerate() {
-        let source = if r.source == "web" { "🌐" } else { "📚" };
+        let source = if r.source == "web" { icon(IconKey::Web) } else { icon(IconKey::Context) };
         let preview: String = r.content.chars().take(60).collect();
         println!("  {}. {} {}...", i + 1, source, preview);
     }
 
     // 6. Deep Research
     println!("\n{}", "=".repeat(60));
-    println!("\n🧪 Test 6: Deep Research");
+    println!("\n{} Test 6: Deep Research", icon(IconKey::Test));
     println!("{}", "-".repeat(60));
     let topic = "Functional Programming";
     println!("Topic: {}", topic);
-    println!("\n🔬 Researching...");
+    println!("\n{} Researching...", icon(IconKey::Research));
     match ace.research(topic).await {
         Ok(report) => {
+            let report = report.to_display_string();
             let lines: Vec<&str> = report.lines().take(15).collect();
             println!("{}", lines.join("\n"));
             println!("...");
@@ -129,26 +161,272 @@ async fn demo_mode(ace: &mut ACEFramework) {
 
     // Final Stats
     println!("\n{}", "=".repeat(60));
-    println!("\n📊 Final Statistics");
+    println!("\n{} Final Statistics", icon(IconKey::Stats));
     println!("{}", "-".repeat(60));
     let stats = ace.get_context_stats();
     println!("  Total bullets: {}", stats.total_bullets);
     println!("  Helpful bullets: {}", stats.helpful_bullets);
     println!("  Context version: {}", stats.version);
     println!("  Avg helpfulness: {:.2}", stats.avg_helpfulness);
-    println!("\n✅ All tests completed!");
+    println!("\n{} All tests completed!", icon(IconKey::Success));
     println!("{}", "=".repeat(60));
 }
 
-async fn interactive_mode(ace: &mut ACEFramework) {
+// One side of a word diff: A shows deletions in red, B shows insertions in green.
+fn render_diff_side(diff: &[DiffToken], left: bool) -> String {
+    diff.iter()
+        .filter_map(|token| match token {
+            DiffToken::Same(word) => Some(word.normal()),
+            DiffToken::Deleted(word) if left => Some(word.red()),
+            DiffToken::Inserted(word) if !left => Some(word.green()),
+            _ => None,
+        })
+        .map(|word| word.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn review_duplicates(ace: &mut ACEFramework) {
+    if let Err(e) = ace.curator_mut() {
+        log_error(&e);
+        return;
+    }
+    let pairs = ace.curator.find_near_duplicates(0.8);
+    if pairs.is_empty() {
+        log_info("No near-duplicate bullets found");
+        return;
+    }
+
+    println!("\n{} {} near-duplicate pair(s):", icon(IconKey::Duplicate), pairs.len());
+    for pair in pairs {
+        let bullets = &ace.curator.get_context().bullets;
+        let (Some(a), Some(b)) = (bullets.get(&pair.bullet_a_id), bullets.get(&pair.bullet_b_id)) else {
+            continue; // one side already merged away
+        };
+        println!("\n{:.2} similarity ({:?})", pair.similarity, pair.method);
+        let diff = diff_strings(&a.content, &b.content);
+        println!("  A: {}", render_diff_side(&diff, true));
+        println!("  B: {}", render_diff_side(&diff, false));
+
+        print!("Merge? (y/n) ");
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+            continue;
+        }
+        if let Some(id) = ace.curator.weaker_of(&pair).map(str::to_string) {
+            if let Ok(curator) = ace.curator_mut() {
+                curator.remove_bullet(&id);
+            }
+            log_success(&format!("Removed bullet {}", id));
+        }
+    }
+}
+
+fn print_aliases(aliases: &HashMap<String, String>) {
+    let mut by_command: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for alias in aliases.keys() {
+        let expanded = expand_alias(&format!("/{}", alias.trim_start_matches('/')), aliases);
+        by_command.entry(expanded).or_default().push(format!("/{}", alias.trim_start_matches('/')));
+    }
+    if by_command.is_empty() {
+        return;
+    }
+    println!("\n  Aliases:");
+    for (command, mut short) in by_command {
+        short.sort();
+        println!("  - '{}' ← {}", command, short.join(", "));
+    }
+}
+
+// Prompt tokens a command is likely to send; research fans out into many calls.
+fn estimated_llm_cost(input: &str) -> usize {
+    if let Some(topic) = input.strip_prefix("/research ") {
+        estimate_tokens(topic) * ResearchDepth::default().call_budget()
+    } else if let Some(query) = input.strip_prefix("/think ").or_else(|| input.strip_prefix("/explain ")) {
+        estimate_tokens(query)
+    } else if input.starts_with('/') || matches!(input, "stats" | "stats --tags" | "help" | "exit" | "quit") {
+        0
+    } else {
+        estimate_tokens(input)
+    }
+}
+
+/// An interactive command whose arguments passed validation.
+#[derive(Debug, PartialEq)]
+enum ParsedCommand<'a> {
+    Research {
+        template: Option<&'a str>,
+        topic: &'a str,
+    },
+    Thinking(bool),
+    Web(bool),
+    Verify(bool),
+    Search {
+        query: &'a str,
+        tag: Option<&'a str>,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        show_facets: bool,
+    },
+    Model(&'a str),
+}
+
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    message: String,
+    usage: &'static str,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, usage: &'static str) -> Self {
+        Self {
+            message: message.into(),
+            usage,
+        }
+    }
+}
+
+const RESEARCH_USAGE: &str = "/research [--template <name>] <topic>";
+const SEARCH_USAGE: &str = "/search [--facets] [--tag <tag>] [--since <date>] <query>";
+const MODEL_USAGE: &str = "/model <model_name>";
+
+/// Validates the arguments of interactive commands before anything reaches
+/// the backend.
+struct CommandParser;
+
+impl CommandParser {
+    /// `Ok(None)` means `input` is not a command this parser validates.
+    fn parse(input: &str) -> Result<Option<ParsedCommand<'_>>, ParseError> {
+        let (command, args) = input.split_once(' ').unwrap_or((input, ""));
+        let args = args.trim();
+        let parsed = match command {
+            "/research" => Self::parse_research(args)?,
+            "/thinking" => ParsedCommand::Thinking(Self::parse_toggle(args, "/thinking on|off")?),
+            "/web" => ParsedCommand::Web(Self::parse_toggle(args, "/web on|off")?),
+            "/verify" => ParsedCommand::Verify(Self::parse_toggle(args, "/verify on|off")?),
+            "/search" => Self::parse_search(args)?,
+            "/model" => Self::parse_model(args)?,
+            _ => return Ok(None),
+        };
+        Ok(Some(parsed))
+    }
+
+    fn parse_research(args: &str) -> Result<ParsedCommand<'_>, ParseError> {
+        let (template, topic) = match args.strip_prefix("--template").filter(|rest| rest.is_empty() || rest.starts_with(' ')) {
+            Some(rest) => {
+                let (name, topic) = rest.trim_start().split_once(' ').unwrap_or((rest.trim(), ""));
+                if name.is_empty() {
+                    return Err(ParseError::new("--template needs a template name", RESEARCH_USAGE));
+                }
+                (Some(name), topic.trim())
+            }
+            None => (None, args),
+        };
+        if topic.chars().count() < 3 {
+            return Err(ParseError::new("Research topic must be at least 3 characters", RESEARCH_USAGE));
+        }
+        Ok(ParsedCommand::Research { template, topic })
+    }
+
+    fn parse_toggle(args: &str, usage: &'static str) -> Result<bool, ParseError> {
+        match args.to_lowercase().as_str() {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            "" => Err(ParseError::new("Expected 'on' or 'off'", usage)),
+            other => Err(ParseError::new(format!("Expected 'on' or 'off', got '{}'", other), usage)),
+        }
+    }
+
+    fn parse_search(args: &str) -> Result<ParsedCommand<'_>, ParseError> {
+        let mut query = args;
+        let mut show_facets = false;
+        let mut tag = None;
+        let mut since = None;
+        loop {
+            if let Some(rest) = query.strip_prefix("--facets") {
+                show_facets = true;
+                query = rest.trim_start();
+            } else if let Some(rest) = query.strip_prefix("--since ") {
+                let rest = rest.trim_start();
+                let (expr, rest) = match rest.strip_prefix('"') {
+                    Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                    None => rest.split_once(' ').unwrap_or((rest, "")),
+                };
+                since = Some(parse_relative_date(expr).ok_or_else(|| {
+                    ParseError::new(
+                        format!("Unrecognized date '{}'. Try \"last 7 days\", \"yesterday\", \"this week\" or \"since 2025-01-31\"", expr),
+                        SEARCH_USAGE,
+                    )
+                })?);
+                query = rest.trim_start();
+            } else if let Some(rest) = query.strip_prefix("--tag ") {
+                let (name, rest) = rest.trim_start().split_once(' ').unwrap_or((rest.trim(), ""));
+                tag = Some(name);
+                query = rest.trim_start();
+            } else {
+                break;
+            }
+        }
+        if query.chars().count() < 2 {
+            return Err(ParseError::new("Search query must be at least 2 characters", SEARCH_USAGE));
+        }
+        Ok(ParsedCommand::Search {
+            query,
+            tag,
+            since,
+            show_facets,
+        })
+    }
+
+    fn parse_model(args: &str) -> Result<ParsedCommand<'_>, ParseError> {
+        if args.is_empty() {
+            return Err(ParseError::new("Missing model name", MODEL_USAGE));
+        }
+        let valid = args
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, ':' | '.' | '_' | '-'));
+        if !valid {
+            return Err(ParseError::new(
+                format!("Invalid model name '{}': use lowercase letters, digits and ':._-'", args),
+                MODEL_USAGE,
+            ));
+        }
+        Ok(ParsedCommand::Model(args))
+    }
+}
+
+async fn interactive_mode(ace: &mut ACEFramework, app_config: &AppConfig) {
     log_info("ACE Interactive Mode");
-    println!("\nCommands: 'stats', 'help', 'exit', '/think', '/search', '/research', '/thinking on|off', '/web on|off'");
+    ace.rate_limiter = Some(RateLimiter::default());
+    let interactive = &app_config.interactive;
+    let plugins: &[CommandPlugin] = match (app_config.enable_command_plugins, app_config.commands.is_empty()) {
+        (_, true) => &[],
+        (true, false) => {
+            log_warning(&format!(
+                "{} command plugin(s) enabled: they run arbitrary shell commands from ace.toml and installed plugins",
+                app_config.commands.len()
+            ));
+            &app_config.commands
+        }
+        (false, false) => {
+            log_warning("Command plugins are ignored; set enable_command_plugins = true in ace.toml to run them");
+            &[]
+        }
+    };
+    let session_id = app_config
+        .session_name
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    println!("\nCommands: 'stats', 'help', 'exit', '/think', '/search', '/research', '/qa', '/image', '/thinking on|off', '/web on|off', '/verify on|off', '/model <name>', '/bullets', '/pin|unpin <id>', '/edit <id>', '/rollback <id> [n]', '/helpful', '/unhelpful', '/duplicates', '/agenda', '/pipeline', '/explain', '/context-window', '/archive list|restore <id>', '/export anki|obsidian|pdf <path>', '/import obsidian <vault>', '/ingest <file>', '/sync pull <url>'");
     println!("{}", "-".repeat(60));
 
     let mut thinking_mode = false;
     let stdin = io::stdin();
     loop {
-        print!("\n👤 You: ");
+        if ace.quality.is_degrading() {
+            println!("\n{} Context quality degrading: review /bullets and /duplicates", icon(IconKey::Warning));
+        }
+        print!("\n{} You: ", icon(IconKey::User));
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
@@ -156,87 +434,511 @@ async fn interactive_mode(ace: &mut ACEFramework) {
             break;
         }
 
-        let input = input.trim();
+        ace.apply_backend_swaps();
+        match ace.apply_sync_changes().await {
+            Some(Ok(report)) if report.added + report.updated > 0 => log_info(&format!(
+                "Synced: {} added, {} updated, {} conflicts",
+                report.added, report.updated, report.conflicts
+            )),
+            Some(Err(e)) => log_warning(&format!("Sync failed: {}", e)),
+            _ => {}
+        }
+        let input = expand_alias(input.trim(), &interactive.aliases);
+        let input = input.as_str();
         if input.is_empty() {
             continue;
         }
 
+        let input = match input.strip_prefix("/qa ") {
+            Some(query) => match ace.answer_from_context(query.trim()) {
+                Some(answer) => {
+                    println!("\n{} From context:\n{}", icon(IconKey::Context), answer);
+                    continue;
+                }
+                None => {
+                    log_info("No direct answer in context, asking the LLM");
+                    query.trim()
+                }
+            },
+            None => input,
+        };
+
+        let command = match CommandParser::parse(input) {
+            Ok(command) => command,
+            Err(e) => {
+                log_error(&e.message);
+                println!("  Usage: {}", e.usage);
+                continue;
+            }
+        };
+
+        if let Err(e) = ace.check_rate_limit(estimated_llm_cost(input)) {
+            log_error(&e);
+            continue;
+        }
+
+        if let Some(command) = command {
+            match command {
+                ParsedCommand::Thinking(on) => {
+                    thinking_mode = on;
+                    log_success(if on { "Native thinking mode enabled" } else { "Native thinking mode disabled" });
+                }
+                ParsedCommand::Web(on) => {
+                    ace.web_search_enabled = on;
+                    if on {
+                        log_success(&format!("{} Web search enabled (like OpenAI)", icon(IconKey::Web)));
+                    } else {
+                        log_success("Web search disabled");
+                    }
+                }
+                ParsedCommand::Verify(on) => {
+                    ace.verification.enabled = on;
+                    log_success(if on {
+                        "Context-grounded verification enabled"
+                    } else {
+                        "Context-grounded verification disabled"
+                    });
+                }
+                ParsedCommand::Model(model) => {
+                    if let Err(e) = ace.switch_model(model).await {
+                        log_error(&e);
+                    }
+                }
+                ParsedCommand::Search {
+                    query,
+                    tag,
+                    since,
+                    show_facets,
+                } => {
+                    print!("\n{} Searching...\n", icon(IconKey::Search));
+                    let page = ace.search_query_faceted(query, tag, since).await;
+                    print!("{}", page.to_display_string());
+                    if show_facets {
+                        print!("{}", page.facets_display_string());
+                    }
+                    println!();
+                }
+                ParsedCommand::Research { template, topic } => {
+                    let template = match template {
+                        Some(name) => {
+                            let mut path = Path::new("research_templates").join(format!("{}.toml", name));
+                            if !path.exists() {
+                                path = PluginStore::default().find_prompt_template(name).unwrap_or(path);
+                            }
+                            match ResearchTemplate::load(&path) {
+                                Ok(template) => template,
+                                Err(e) => {
+                                    log_error(&e);
+                                    continue;
+                                }
+                            }
+                        }
+                        None => ResearchTemplate::default(),
+                    };
+                    print!("\n{} Researching:\n", icon(IconKey::Research));
+                    let mut events = match ace.research_with_progress(topic, template) {
+                        Ok(events) => events,
+                        Err(e) => {
+                            log_error(&format!("Error: {}", e));
+                            continue;
+                        }
+                    };
+                    while let Some(event) = events.recv().await {
+                        match event {
+                            ResearchProgressEvent::Done(report) => {
+                                println!("{}\n{}", "=".repeat(60), report.to_markdown());
+                            }
+                            ResearchProgressEvent::Failed(e) => log_error(&format!("Error: {}", e)),
+                            event => {
+                                if let Some(line) = event.describe() {
+                                    println!("{}", line);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            continue;
+        }
+
         match input {
             "exit" | "quit" => {
+                let events = &ace.search_analytics.log;
+                if !events.is_empty() && !ace.is_dry_run() {
+                    if let Err(e) = append_search_events(Path::new(SEARCH_ANALYTICS_FILE), events) {
+                        log_error(&e);
+                    }
+                }
                 log_info("Goodbye!");
                 break;
             }
-            "stats" => {
+            "stats" | "stats --tags" => {
+                ace.finish_pending_learns().await;
                 let stats = ace.get_context_stats();
-                println!("\n📊 Context Statistics:");
+                println!("\n{} Context Statistics:", icon(IconKey::Stats));
                 println!("  Total bullets: {}", stats.total_bullets);
                 println!("  Helpful bullets: {}", stats.helpful_bullets);
+                println!("  Pinned bullets: {}", stats.pinned_bullets);
                 println!("  Version: {}", stats.version);
                 println!("  Avg helpfulness: {:.2}", stats.avg_helpfulness);
+                if stats.quality_warning {
+                    println!("  Quality: degrading (recent answers mostly rated unhelpful)");
+                }
+                if !ace.success_rate_window.is_empty() {
+                    let alarm = if stats.low_success_alarm { " (alarm: mostly failing)" } else { "" };
+                    println!("  Trajectory success: {:.0}%{}", stats.recent_success_rate * 100.0, alarm);
+                }
+                let health = ace.get_context_health();
+                let mut components: Vec<_> = health.breakdown.iter().collect();
+                components.sort_by(|a, b| a.0.cmp(b.0));
+                let components: Vec<String> = components.iter().map(|(k, v)| format!("{} {:.2}", k, v)).collect();
+                println!("  Health: {:.0}/100 ({})", health.score * 100.0, components.join(", "));
+                if !stats.tool_invocations.is_empty() {
+                    let mut tools: Vec<_> = stats.tool_invocations.iter().collect();
+                    tools.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                    let tools: Vec<String> = tools
+                        .iter()
+                        .map(|(tool, calls)| {
+                            let avg = stats.avg_tool_latency.get(*tool).copied().unwrap_or(0.0);
+                            format!("{} {} ({:.0} ms avg)", tool, calls, avg)
+                        })
+                        .collect();
+                    println!("  Tools: {}", tools.join(", "));
+                }
+                if input == "stats --tags" {
+                    println!("\n  {:<20} {:>8} {:>12} {:>6}", "Tag", "Bullets", "Helpfulness", "Uses");
+                    for (tag, tag_stats) in stats.tags_by_helpfulness() {
+                        println!(
+                            "  {:<20} {:>8} {:>12.2} {:>6}",
+                            tag, tag_stats.bullet_count, tag_stats.avg_helpfulness, tag_stats.total_uses
+                        );
+                    }
+                }
             }
             "help" => {
-                println!("\n📖 ACE Framework Help");
+                println!("\n{} ACE Framework Help", icon(IconKey::Help));
                 println!("  - Ask any question naturally");
                 println!("  - 'stats' - Show context statistics");
+                println!("  - 'stats --tags' - Per-tag breakdown sorted by helpfulness");
                 println!("  - '/think <query>' - Deep thinking mode");
                 println!("  - '/search <query>' - Search in context/web");
+                println!("    Wrap phrases in quotes for exact matches: /search \"context engineering\"");
+                println!("    Combine terms with AND, OR, NOT and parentheses: /search Rust AND NOT Python");
+                println!("  - '/search --facets <query>' - Also show result counts per tag");
+                println!("  - '/search --tag <tag> <query>' - Only search bullets with that tag");
+                println!("  - '/search --since \"last 7 days\" <query>' - Only bullets learned since then");
                 println!("  - '/research <topic>' - Deep research mode");
+                println!("  - '/research --template <name> <topic>' - Use research_templates/<name>.toml or a plugin's template");
                 println!("  - '/thinking on|off' - Toggle native thinking mode");
                 println!("  - '/web on|off' - Toggle web search (like OpenAI)");
+                println!("  - '/verify on|off' - Flag answers that contradict learned context");
+                println!("  - '/model <name>' - Switch Ollama model, keeping learned context");
+                println!("  - '/helpful' / '/unhelpful' - Rate the last answer and the bullets it used");
+                println!("  - '/duplicates' - Review and merge near-duplicate bullets");
+                println!("  - '/bullets' - List context bullets ({} = pinned)", icon(IconKey::Pin));
+                println!("  - '/pin <id>' / '/unpin <id>' - Protect a bullet from eviction (id prefix is enough)");
+                println!("  - '/edit <id>' - Replace a bullet's content, keeping previous versions");
+                println!("  - '/rollback <id> [n]' - Restore a bullet's content from n edits ago (default 1)");
+                println!("  - '/archive list' - Show archived low-scoring bullets");
+                println!("  - '/archive restore <id>' - Move an archived bullet back into context");
+                println!("  - '/export anki <file>' - Export context as an Anki CSV deck");
+                println!("  - '/export obsidian <vault>' - Export context as Obsidian notes, one per tag");
+                println!("  - '/export pdf <file>' - Export this session's transcript as a PDF");
+                println!("  - '/import obsidian <vault>' - Import ## sections of an Obsidian vault as bullets");
+                println!("  - '/ingest <file>' - Learn bullets from a .txt, .md or .rs document");
+                println!("  - '/sync pull <url>' - Pull new and updated bullets from another ACE instance");
+                println!("  - '/agenda' - List unfinished multi-step tasks");
+                println!("  - '/resume <id>' - Continue an unfinished task");
+                println!("  - '/pipeline <name> <input>' - Run pipelines/<name>.toml");
+                println!("  - '/qa <query>' - Answer directly from learned context, falling back to the LLM");
+                println!("  - '/image <path> [question]' - Ask a vision model about a PNG/JPEG (max 10 MB)");
+                println!("  - '/explain <query>' - Answer and list the context bullets retrieved");
+                println!("  - '/context-window [query]' - Show how the context window budget is used");
+                println!("  - Ctrl+C while answering - Stop the answer and keep what was received");
                 println!("  - 'exit' - Exit system");
+                if !plugins.is_empty() {
+                    println!("\n  Command plugins:");
+                    for plugin in plugins {
+                        let description = if plugin.description.is_empty() { &plugin.script } else { &plugin.description };
+                        println!("  - '/{}' - {}", plugin.name.trim_start_matches('/'), description);
+                    }
+                }
+                print_aliases(&interactive.aliases);
+            }
+            "/duplicates" => review_duplicates(ace),
+            "/helpful" | "/unhelpful" => {
+                match ace.record_feedback(input == "/helpful") {
+                    Ok(rated) => log_success(&format!("Feedback recorded on {} bullet(s)", rated)),
+                    Err(e) => log_error(&e),
+                }
+            }
+            "/bullets" => {
+                let mut bullets: Vec<&ContextBullet> = ace.curator.get_context().bullets.values().collect();
+                if bullets.is_empty() {
+                    log_info("No bullets yet");
+                }
+                bullets.sort_by_key(|b| (std::cmp::Reverse(b.pinned), std::cmp::Reverse(b.helpful_count - b.harmful_count)));
+                for bullet in bullets {
+                    println!(
+                        "  {}{} [{:+}] {}",
+                        if bullet.pinned { format!("{} ", icon(IconKey::Pin)) } else { String::new() },
+                        &bullet.id[..8.min(bullet.id.len())],
+                        bullet.helpful_count - bullet.harmful_count,
+                        bullet.content.chars().take(80).collect::<String>()
+                    );
+                }
             }
-            _ if input.starts_with("/thinking ") => {
-                let mode = &input[10..].trim().to_lowercase();
-                match mode.as_str() {
-                    "on" => {
-                        thinking_mode = true;
-                        log_success("Native thinking mode enabled");
+            _ if input.starts_with("/edit ") => {
+                if let Err(e) = ace.curator_mut() {
+                    log_error(&e);
+                    continue;
+                }
+                let id = match ace.curator.resolve_id_prefix(input["/edit ".len()..].trim()) {
+                    Ok(id) => id,
+                    Err(e) => {
+                        log_error(&e);
+                        continue;
                     }
-                    "off" => {
-                        thinking_mode = false;
-                        log_success("Native thinking mode disabled");
+                };
+                println!("Current: {}", ace.curator.get_context().bullets[&id].content);
+                print!("New content (empty to cancel): ");
+                io::stdout().flush().unwrap();
+                let mut new_content = String::new();
+                if stdin.read_line(&mut new_content).is_err() || new_content.trim().is_empty() {
+                    log_info("Edit cancelled");
+                    continue;
+                }
+                match ace.curator_mut().and_then(|curator| curator.edit_bullet(&id, new_content.trim())) {
+                    Ok(()) => log_success(&format!("Updated bullet {}", id)),
+                    Err(e) => log_error(&e),
+                }
+            }
+            _ if input.starts_with("/rollback ") => {
+                let mut parts = input["/rollback ".len()..].split_whitespace();
+                let prefix = parts.next().unwrap_or("");
+                let steps = match parts.next().map(str::parse::<usize>).unwrap_or(Ok(1)) {
+                    Ok(steps) => steps,
+                    Err(_) => {
+                        log_error("Usage: /rollback <id> [steps]");
+                        continue;
                     }
-                    _ => log_error("Use: /thinking on or /thinking off"),
+                };
+                let result = ace.curator_mut().and_then(|curator| {
+                    let id = curator.resolve_id_prefix(prefix)?;
+                    curator.rollback_bullet(&id, steps).map(|_| id)
+                });
+                match result {
+                    Ok(id) => log_success(&format!("Rolled bullet {} back {} step(s)", id, steps)),
+                    Err(e) => log_error(&e),
                 }
             }
-            _ if input.starts_with("/web ") => {
-                let mode = &input[5..].trim().to_lowercase();
-                match mode.as_str() {
-                    "on" => {
-                        ace.web_search_enabled = true;
-                        log_success("🌐 Web search enabled (like OpenAI)");
+            _ if input.starts_with("/pin ") || input.starts_with("/unpin ") => {
+                let (pin, prefix) = match input.strip_prefix("/pin ") {
+                    Some(prefix) => (true, prefix.trim()),
+                    None => (false, input["/unpin ".len()..].trim()),
+                };
+                let result = ace.curator_mut().and_then(|curator| {
+                    let id = curator.resolve_id_prefix(prefix)?;
+                    if pin {
+                        curator.pin_bullet(&id)?;
+                    } else {
+                        curator.unpin_bullet(&id)?;
                     }
-                    "off" => {
-                        ace.web_search_enabled = false;
-                        log_success("Web search disabled");
+                    Ok(id)
+                });
+                match result {
+                    Ok(id) => log_success(&format!("{} bullet {}", if pin { "Pinned" } else { "Unpinned" }, id)),
+                    Err(e) => log_error(&e),
+                }
+            }
+            "/archive list" => {
+                if ace.curator.archive.is_empty() {
+                    log_info("Archive is empty");
+                }
+                for (id, archived) in &ace.curator.archive {
+                    println!(
+                        "  {} [{}] {} - {}",
+                        id,
+                        archived.archived_at.format("%Y-%m-%d"),
+                        archived.bullet.content.chars().take(80).collect::<String>(),
+                        archived.reason
+                    );
+                }
+            }
+            _ if input.starts_with("/archive restore ") => {
+                let id = input["/archive restore ".len()..].trim();
+                match ace.curator_mut().and_then(|curator| curator.restore_from_archive(id)) {
+                    Ok(()) => log_success(&format!("Restored bullet {}", id)),
+                    Err(e) => log_error(&e),
+                }
+            }
+            _ if input.starts_with("/sync pull ") => {
+                let remote = input["/sync pull ".len()..].trim();
+                match ace.pull_from_remote(remote).await {
+                    Ok(report) => log_success(&format!(
+                        "Synced from {}: {} added, {} updated, {} conflicts",
+                        remote, report.added, report.updated, report.conflicts
+                    )),
+                    Err(e) => log_error(&e),
+                }
+            }
+            _ if input.starts_with("/export ") && ace.is_dry_run() => {
+                log_info(&format!("Dry run: skipping {}", input));
+            }
+            _ if input.starts_with("/export obsidian ") => {
+                let path = Path::new(input["/export obsidian ".len()..].trim());
+                match ace.curator.export_to_obsidian(path) {
+                    Ok(files) => log_success(&format!("Wrote {} notes to {}", files, path.display())),
+                    Err(e) => log_error(&e),
+                }
+            }
+            _ if input.starts_with("/ingest ") => {
+                let path = input["/ingest ".len()..].trim();
+                match ace.ingest_document(Path::new(path), 1000).await {
+                    Ok(report) => log_success(&format!(
+                        "Ingested {}: {} chunks, {} bullets created in {} ms",
+                        path, report.chunks_processed, report.bullets_created, report.elapsed_ms
+                    )),
+                    Err(e) => log_error(&e),
+                }
+            }
+            _ if input.starts_with("/import obsidian ") => {
+                let path = Path::new(input["/import obsidian ".len()..].trim());
+                match ace.curator_mut().and_then(|curator| import_obsidian_vault(path, curator)) {
+                    Ok(report) => log_success(&format!(
+                        "Imported {} bullets from {} notes ({} skipped)",
+                        report.bullets_created, report.files_scanned, report.files_skipped
+                    )),
+                    Err(e) => log_error(&e),
+                }
+            }
+            _ if input.starts_with("/export pdf ") => {
+                let path = Path::new(input["/export pdf ".len()..].trim());
+                match ace.export_transcript_pdf(&session_id, path) {
+                    Ok(()) => log_success(&format!("Wrote transcript of {} exchanges to {}", ace.recordings.len(), path.display())),
+                    Err(e) => log_error(&e),
+                }
+            }
+            _ if input.starts_with("/export anki ") => {
+                let path = input["/export anki ".len()..].trim();
+                let bullets: Vec<_> = ace.curator.get_context().bullets.values().cloned().collect();
+                match std::fs::write(path, bullets_to_anki_csv(&bullets)) {
+                    Ok(()) => log_success(&format!("Exported {} cards to {}", bullets.len(), path)),
+                    Err(e) => log_error(&format!("Cannot write {}: {}", path, e)),
+                }
+            }
+            "/agenda" => {
+                let pending = ace.trajectories.in_progress();
+                if pending.is_empty() {
+                    log_info("No unfinished tasks");
+                } else {
+                    println!("\n{} Unfinished tasks:", icon(IconKey::Tasks));
+                    for t in pending {
+                        println!("  {} - {} ({} steps)", t.id, t.query, t.steps.len());
+                    }
+                    println!("Resume with /resume <id>");
+                }
+            }
+            _ if input.starts_with("/resume ") => {
+                let id = input[8..].trim();
+                match ace.resume_trajectory(id).await {
+                    Ok(t) => {
+                        for (i, step) in t.steps.iter().enumerate() {
+                            println!("  {}. {}", i + 1, step.description);
+                        }
+                        println!("\n{} {}", icon(IconKey::Robot), t.outcome);
+                        if t.success {
+                            log_success("Task completed");
+                        } else {
+                            log_info("Task still in progress; see /agenda");
+                        }
                     }
-                    _ => log_error("Use: /web on or /web off"),
+                    Err(e) => log_error(&e),
                 }
             }
             _ if input.starts_with("/think ") => {
                 let query = &input[7..];
-                print!("\n🧠 Thinking:\n");
+                print!("\n{} Thinking:\n", icon(IconKey::Thinking));
                 match ace.think(query).await {
                     Ok(result) => println!("{}", result),
                     Err(e) => log_error(&format!("Error: {}", e)),
                 }
             }
-            _ if input.starts_with("/search ") => {
-                let query = &input[8..];
-                print!("\n🔍 Searching...\n");
-                let result = ace.search_query(query).await;
-                println!("{}", result);
+            _ if input == "/context-window" || input.starts_with("/context-window ") => {
+                let view = ace.get_context_window_view(input["/context-window".len()..].trim());
+                println!("\n{} Context window ({} tokens):", icon(IconKey::Window), view.total_tokens);
+                println!("  {}", view.to_bar());
+                println!(
+                    "  System {} · Bullets {} · History {} · Query {} · Free {}",
+                    view.system_prompt_tokens,
+                    view.context_bullets_tokens,
+                    view.conversation_history_tokens,
+                    view.query_tokens,
+                    view.remaining_tokens
+                );
             }
-            _ if input.starts_with("/research ") => {
-                let topic = &input[10..];
-                print!("\n🔬 Researching:\n");
-                match ace.research(topic).await {
-                    Ok(result) => println!("{}", result),
+            _ if input.starts_with("/image ") => {
+                let args = input["/image ".len()..].trim();
+                let (path, question) = args.split_once(' ').unwrap_or((args, ""));
+                let question = if question.trim().is_empty() { "Describe this image." } else { question.trim() };
+                print!("\n{} Looking at {}...\n", icon(IconKey::Image), path);
+                match ace.ask_about_image(Path::new(path), question).await {
+                    Ok(answer) => println!("\n{} ACE:\n{}", icon(IconKey::Robot), answer),
                     Err(e) => log_error(&format!("Error: {}", e)),
                 }
             }
+            _ if input.starts_with("/explain ") => {
+                let query = input[9..].trim();
+                match ace.process_query_with_explanation(query).await {
+                    Ok(explained) => {
+                        println!("\n{} ACE:\n{}", icon(IconKey::Robot), explained.response);
+                        println!("\nContext used:");
+                        if explained.contributing_bullets.is_empty() {
+                            println!("  (none)");
+                        }
+                        for (bullet, score) in &explained.contributing_bullets {
+                            println!("  [{:.2}] {}", score, bullet.content.chars().take(100).collect::<String>());
+                        }
+                    }
+                    Err(e) => log_error(&format!("Error: {}", e)),
+                }
+            }
+            _ if input.starts_with("/pipeline ") => {
+                let (name, pipeline_input) = input[10..].trim().split_once(' ').unwrap_or((input[10..].trim(), ""));
+                let path = Path::new("pipelines").join(format!("{}.toml", name));
+                let pipeline = match AcePipeline::load(&path) {
+                    Ok(pipeline) => pipeline,
+                    Err(e) => {
+                        log_error(&e);
+                        continue;
+                    }
+                };
+                match pipeline.run(pipeline_input.trim(), ace).await {
+                    Ok(outputs) => {
+                        for output in &outputs {
+                            log_success(&format!("Step '{}' done", output.step));
+                        }
+                        if let Some(last) = outputs.last() {
+                            println!("\n{}", last.text);
+                        }
+                    }
+                    Err(e) => log_error(&e),
+                }
+            }
             _ => {
-                print!("\n🤖 ACE:\n");
+                if let Some((plugin, args)) = match_command_plugin(input, plugins) {
+                    if !plugin_args_match(plugin, args) {
+                        log_error(&format!("Arguments don't match {}", plugin.args_pattern));
+                    } else {
+                        match run_command_plugin(plugin, args, &session_id).await {
+                            Ok(output) => print!("\n{}", output),
+                            Err(e) => log_error(&e),
+                        }
+                    }
+                    continue;
+                }
+
+                print!("\n{} ACE:\n", icon(IconKey::Robot));
                 io::stdout().flush().unwrap();
 
                 let stream_result = ace.process_query_stream(input).await;
@@ -244,29 +946,58 @@ async fn interactive_mode(ace: &mut ACEFramework) {
                 match stream_result {
                     Ok(mut stream) => {
                         let mut full_response = String::new();
-                        while let Some(result) = stream.next().await {
+                        let mut interrupted = false;
+                        loop {
+                            let result = tokio::select! {
+                                result = stream.next() => result,
+                                _ = tokio::signal::ctrl_c() => {
+                                    interrupted = true;
+                                    None
+                                }
+                            };
                             match result {
-                                Ok(chunk) => {
+                                Some(Ok(chunk)) => {
                                     print!("{}", chunk);
                                     full_response.push_str(&chunk);
                                     io::stdout().flush().unwrap();
                                 }
-                                Err(e) => {
+                                Some(Err(e)) => {
                                     log_error(&format!("Stream error: {}", e));
                                     break;
                                 }
+                                None => break,
                             }
                         }
                         println!();
 
-                        // Learn from this interaction
-                        if !thinking_mode {
-                            ace.learn_from_interaction(input, &full_response).await;
+                        // Learn from this interaction; keep interrupted answers as partial
+                        if interrupted {
+                            log_info("Interrupted, keeping the partial answer");
+                            if !thinking_mode && !full_response.is_empty() {
+                                if let Err(e) = ace.learn_from_partial_interaction(input, &full_response).await {
+                                    log_error(&e);
+                                }
+                            }
+                        } else if !thinking_mode {
+                            ace.spawn_learn_from_interaction(input.to_string(), full_response.clone());
+                            if ace.quality_check_enabled && !full_response.is_empty() {
+                                match ace.process_quality_check(input, &full_response).await {
+                                    Ok(assessment) => println!(
+                                        "{} Quality: {:.1}/5 (accuracy {}, completeness {}, conciseness {})",
+                                        icon(IconKey::Stats),
+                                        assessment.average(),
+                                        assessment.accuracy,
+                                        assessment.completeness,
+                                        assessment.conciseness
+                                    ),
+                                    Err(e) => log_warning(&format!("Quality check failed: {}", e)),
+                                }
+                            }
                         }
 
                         let stats = ace.get_context_stats();
                         if stats.total_bullets > 0 {
-                            println!("💡 Context: {} bullets learned", stats.total_bullets);
+                            println!("{} Context: {} bullets learned", icon(IconKey::Learned), stats.total_bullets);
                         }
                     }
                     Err(e) => log_error(&format!("Error: {}", e)),
@@ -276,19 +1007,258 @@ async fn interactive_mode(ace: &mut ACEFramework) {
     }
 }
 
+fn prompt_line(question: &str) -> String {
+    print!("{}", question);
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+    answer.trim().to_string()
+}
+
+/// First-run setup: picks a model from the local Ollama server and writes
+/// `ace.toml`. Returns whether the user wants to start with the demo.
+async fn onboarding_wizard(config_path: &Path) -> types::Result<bool> {
+    log_info("Welcome to ACE! No ace.toml found, let's set things up (skip with --no-wizard).");
+
+    let defaults = OllamaConfig::default();
+    let client = OllamaClient::new(defaults.clone());
+    let models = match client.initialize().await {
+        Ok(_) => {
+            log_success(&format!("Ollama is running at {}", defaults.url));
+            client.list_models().await.unwrap_or_else(|e| {
+                log_error(&format!("Could not list models: {}", e));
+                Vec::new()
+            })
+        }
+        Err(e) => {
+            log_error(&format!("Ollama is not reachable at {}: {}", defaults.url, e));
+            log_info("Start it with `ollama serve`; you can still pick a model now.");
+            Vec::new()
+        }
+    };
+
+    let model = if models.is_empty() {
+        let answer = prompt_line(&format!("Model name [{}]: ", defaults.model));
+        if answer.is_empty() {
+            defaults.model
+        } else {
+            answer
+        }
+    } else {
+        println!("\nAvailable models:");
+        for (i, name) in models.iter().enumerate() {
+            println!("  {}. {}", i + 1, name);
+        }
+        loop {
+            let answer = prompt_line(&format!("Select a model [1-{}, default 1]: ", models.len()));
+            if answer.is_empty() {
+                break models[0].clone();
+            }
+            match answer.parse::<usize>() {
+                Ok(n) if (1..=models.len()).contains(&n) => break models[n - 1].clone(),
+                _ => log_error("Enter one of the numbers above"),
+            }
+        }
+    };
+    let session_name = prompt_line("Session name (optional): ");
+
+    let mut table = toml::Table::new();
+    table.insert("first_run_complete".to_string(), true.into());
+    table.insert("model".to_string(), model.into());
+    if !session_name.is_empty() {
+        table.insert("session_name".to_string(), session_name.into());
+    }
+    let text = toml::to_string(&table).map_err(|e| e.to_string())?;
+    std::fs::write(config_path, text).map_err(|e| format!("Cannot write {}: {}", config_path.display(), e))?;
+    log_success(&format!("Wrote {}", config_path.display()));
+
+    let answer = prompt_line("Run the demo now? [y/N]: ");
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+fn run_credentials_command(args: &[String], app_config: &AppConfig) {
+    const USAGE: &str = "Usage: ace credentials set --service <name> --key <key> | ace credentials get --service <name>";
+    let Some(service) = flag_value(args, "--service") else {
+        log_error(USAGE);
+        return;
+    };
+    let secrets = SecretStore::default();
+    match args.first().map(String::as_str) {
+        Some("set") => {
+            let Some(key) = flag_value(args, "--key") else {
+                log_error(USAGE);
+                return;
+            };
+            match secrets.set_api_key(service, key) {
+                Ok(()) => log_success(&format!("Stored API key for '{}' in the system keychain", service)),
+                Err(e) => log_error(&e),
+            }
+        }
+        Some("get") => match resolve_api_key(app_config, &secrets, service) {
+            Ok(key) => println!("{}", key),
+            Err(e) => log_error(&e),
+        },
+        _ => log_error(USAGE),
+    }
+}
+
+async fn run_plugin_command(args: &[String], dry_run: bool) {
+    const USAGE: &str = "Usage: ace plugin install <path_or_url> | ace plugin list | ace plugin remove <name>";
+    let store = PluginStore::default();
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("install" | "remove"), Some(target)) if dry_run => {
+            log_info(&format!("Dry run: would {} plugin {}", args[0], target));
+        }
+        (Some("install"), Some(source)) => match store.install(source).await {
+            Ok(manifest) => log_success(&format!(
+                "Installed {} {} ({} tools, {} prompt templates{})",
+                manifest.name,
+                manifest.version,
+                manifest.tools.len(),
+                manifest.prompt_templates.len(),
+                if manifest.seed_bullets.is_some() { ", seed bullets" } else { "" }
+            )),
+            Err(e) => log_error(&e),
+        },
+        (Some("list"), _) => {
+            let plugins = store.list();
+            if plugins.is_empty() {
+                println!("No plugins installed in {}", store.root.display());
+            }
+            for (_, manifest) in plugins {
+                println!("{} {} - {}", manifest.name, manifest.version, manifest.description);
+            }
+        }
+        (Some("remove"), Some(name)) => match store.remove(name) {
+            Ok(()) => log_success(&format!("Removed plugin {}", name)),
+            Err(e) => log_error(&e),
+        },
+        _ => log_error(USAGE),
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = std::env::args().collect();
-    let mode = if args.len() > 1 && args[1] == "demo" {
+    if args.iter().any(|a| a == "--no-emoji") {
+        set_display_config(DisplayConfig::ascii());
+    }
+    let mut mode = if args.iter().skip(1).any(|a| a == "demo") {
         "demo"
     } else {
         "interactive"
     };
+    let mut init = if args.iter().any(|a| a == "--wait-for-ollama") {
+        InitConfig::default()
+    } else {
+        InitConfig::single_attempt()
+    };
+    init.auto_pull = args.iter().any(|a| a == "--auto-pull");
+
+    let subcommand = matches!(args.get(1).map(String::as_str), Some("credentials" | "export-obsidian" | "ingest" | "plugin"));
+    let config_path = Path::new(APP_CONFIG_FILE);
+    let skip_wizard = args.iter().any(|a| a == "--no-wizard" || a == "--dry-run");
+    if !subcommand && !config_path.exists() && !skip_wizard {
+        match onboarding_wizard(config_path).await {
+            Ok(true) => mode = "demo",
+            Ok(false) => {}
+            Err(e) => log_error(&format!("Setup failed: {}", e)),
+        }
+    }
+
+    let mut app_config = match load_app_config(config_path) {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            log_error(&e);
+            return;
+        }
+    };
+    if let Err(e) = init_logging(app_config.log_format, app_config.log_file.as_deref()) {
+        log_error(&e);
+    }
+    print_telemetry_notice(&app_config);
+    let dry_run = app_config.dry_run.is_enabled() || args.iter().any(|a| a == "--dry-run");
+
+    if args.get(1).map(String::as_str) == Some("credentials") {
+        run_credentials_command(&args[2..], &app_config);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("plugin") {
+        run_plugin_command(&args[2..], dry_run).await;
+        return;
+    }
+
+    let installed_plugins = PluginStore::default().list();
+    for (_, manifest) in &installed_plugins {
+        for tool in &manifest.tools {
+            let name = tool.name.trim_start_matches('/');
+            if app_config.commands.iter().any(|c| c.name.trim_start_matches('/') == name) {
+                log_warning(&format!("Plugin '{}': command /{} already defined, skipping", manifest.name, name));
+            } else {
+                app_config.commands.push(tool.clone());
+            }
+        }
+    }
 
-    let config = OllamaConfig::default();
+    if !dry_run {
+        install_emergency_dump(EMERGENCY_DUMP_FILE);
+    }
+    let mut config = OllamaConfig::default();
+    if let Some(model) = &app_config.model {
+        config.model = model.clone();
+    }
+    config.embed_model = app_config.embed_model.clone();
+    config.dry_run = dry_run;
+    if let Some(session) = &app_config.session_name {
+        log_info(&format!("Session: {}", session));
+    }
     let mut ace = ACEFramework::new(config);
+    ace.quality_check_enabled = app_config.quality_check_enabled;
+    if dry_run {
+        ace.in_dry_run_mode();
+        log_info("Dry-run mode: no LLM calls, empty web results; context, exports and downloads are not written to disk");
+    }
+
+    if let Some(seed) = &app_config.warm_from_seed {
+        match ace.curator.warm_from_seed(seed) {
+            Ok(count) => log_success(&format!("Context warmed with {} seed bullets.", count)),
+            Err(e) => log_error(&format!("Failed to load seed file: {}", e)),
+        }
+    }
+    for (dir, manifest) in &installed_plugins {
+        if let Some(seed) = &manifest.seed_bullets {
+            match ace.curator.warm_from_seed(&dir.join(seed)) {
+                Ok(count) => log_success(&format!("Plugin '{}' added {} seed bullets.", manifest.name, count)),
+                Err(e) => log_error(&format!("Plugin '{}': failed to load seed bullets: {}", manifest.name, e)),
+            }
+        }
+    }
+
+    // Exports what the seeds loaded; nothing else persists between runs
+    if args.get(1).map(String::as_str) == Some("export-obsidian") {
+        let Some(path) = args.get(2) else {
+            log_error("Usage: ace export-obsidian <path>");
+            return;
+        };
+        if dry_run {
+            log_info(&format!("Dry run: would write Obsidian notes to {}", path));
+            return;
+        }
+        match ace.curator.export_to_obsidian(Path::new(path)) {
+            Ok(files) => log_success(&format!("Wrote {} notes to {}", files, path)),
+            Err(e) => log_error(&e),
+        }
+        return;
+    }
 
-    match ace.initialize().await {
+    match ace.initialize(&init).await {
         Ok(_) => {}
         Err(e) => {
             log_error(&format!("Failed to initialize: {}", e));
@@ -296,11 +1266,39 @@ async fn main() {
         }
     }
 
+    ace.watch_config(config_path);
+    if let (Some(remote), false) = (&app_config.sync.remote, dry_run) {
+        ace.watch_sync_remote(remote, SyncPoller::default(), app_config.sync.webhook.clone());
+    }
+    if args.iter().any(|a| a == "--read-only") {
+        ace.in_read_only_mode();
+        log_info("Read-only mode: answering from learned context via /qa and /search; nothing is learned");
+    }
+
+    // The context lives in memory, so ingesting continues into the session
+    if args.get(1).map(String::as_str) == Some("ingest") {
+        let Some(path) = args.get(2) else {
+            log_error("Usage: ace ingest <file> [--chunk-size <chars>]");
+            return;
+        };
+        let chunk_size = flag_value(&args, "--chunk-size").and_then(|n| n.parse().ok()).unwrap_or(1000);
+        match ace.ingest_document(Path::new(path), chunk_size).await {
+            Ok(report) => log_success(&format!(
+                "Ingested {}: {} chunks, {} bullets created in {} ms",
+                path, report.chunks_processed, report.bullets_created, report.elapsed_ms
+            )),
+            Err(e) => log_error(&e),
+        }
+    }
+
     if mode == "demo" {
         demo_mode(&mut ace).await;
     } else {
-        interactive_mode(&mut ace).await;
+        interactive_mode(&mut ace, &app_config).await;
     }
 
+    if let Err(e) = ace.shutdown().await {
+        log_error(&format!("Failed to save context: {}", e));
+    }
     log_success("ACE Framework shutdown complete");
 }
@@ -0,0 +1,70 @@
+// ACE Obsidian - import/export of Markdown vaults
+#![allow(dead_code)]
+use crate::ace::{ACECurator, ContextStore};
+use crate::functional_core::{bullets_to_obsidian_notes, create_bullet, parse_obsidian_note};
+use crate::types::*;
+use std::path::{Path, PathBuf};
+
+fn markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if hidden {
+            continue; // .obsidian, .trash, ...
+        }
+        if path.is_dir() {
+            markdown_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Adds one bullet per `##` section of every note in the vault.
+pub fn import_obsidian_vault(vault_path: &Path, curator: &mut ACECurator) -> Result<ImportReport> {
+    let mut files = Vec::new();
+    markdown_files(vault_path, &mut files)?;
+    files.sort();
+
+    let mut report = ImportReport::default();
+    let mut bullets = Vec::new();
+    for path in files {
+        report.files_scanned += 1;
+        let text = std::fs::read_to_string(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let title = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        match parse_obsidian_note(title, &text) {
+            Some(sections) => {
+                bullets.extend(sections.into_iter().map(|(content, tags)| create_bullet(content, tags)));
+            }
+            None => report.files_skipped += 1,
+        }
+    }
+
+    report.bullets_created = bullets.len();
+    curator.apply_delta(&DeltaUpdate {
+        bullets,
+        timestamp: chrono::Utc::now(),
+    });
+    Ok(report)
+}
+
+impl ACECurator {
+    /// Writes one note per top-level tag into `vault_path`, overwriting
+    /// existing files. Returns the number of files written.
+    pub fn export_to_obsidian(&self, vault_path: &Path) -> Result<usize> {
+        std::fs::create_dir_all(vault_path)
+            .map_err(|e| format!("Cannot create {}: {}", vault_path.display(), e))?;
+        let bullets: Vec<ContextBullet> = self.get_context().bullets.values().cloned().collect();
+        let notes = bullets_to_obsidian_notes(&bullets);
+        for (name, markdown) in &notes {
+            let path = vault_path.join(format!("{}.md", name));
+            std::fs::write(&path, markdown).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        }
+        Ok(notes.len())
+    }
+}
@@ -0,0 +1,196 @@
+// ACE Pipelines - chain framework operations, each step's output feeding the next
+#![allow(dead_code)]
+use crate::ace::ACEFramework;
+use crate::types::*;
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct PipelineInput {
+    /// The text the pipeline was started with.
+    pub original: String,
+    /// Output of the previous step (or `original` for the first step).
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PipelineOutput {
+    pub step: String,
+    pub text: String,
+}
+
+#[async_trait]
+pub trait PipelineStep: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn execute(&self, input: PipelineInput, framework: &mut ACEFramework) -> Result<PipelineOutput>;
+}
+
+/// Answers the input as a normal query.
+pub struct QueryStep;
+
+#[async_trait]
+impl PipelineStep for QueryStep {
+    fn name(&self) -> &str {
+        "query"
+    }
+
+    async fn execute(&self, input: PipelineInput, framework: &mut ACEFramework) -> Result<PipelineOutput> {
+        let mut stream = Box::pin(framework.process_query_stream(&input.text).await?);
+        let mut text = String::new();
+        while let Some(chunk) = stream.next().await {
+            text.push_str(&chunk?);
+        }
+        Ok(PipelineOutput {
+            step: self.name().to_string(),
+            text,
+        })
+    }
+}
+
+/// Runs deep research on the input and passes the report on as markdown.
+pub struct ResearchStep;
+
+#[async_trait]
+impl PipelineStep for ResearchStep {
+    fn name(&self) -> &str {
+        "research"
+    }
+
+    async fn execute(&self, input: PipelineInput, framework: &mut ACEFramework) -> Result<PipelineOutput> {
+        let report = framework.research(&input.text).await?;
+        Ok(PipelineOutput {
+            step: self.name().to_string(),
+            text: report.to_markdown(),
+        })
+    }
+}
+
+pub struct ThinkStep;
+
+#[async_trait]
+impl PipelineStep for ThinkStep {
+    fn name(&self) -> &str {
+        "think"
+    }
+
+    async fn execute(&self, input: PipelineInput, framework: &mut ACEFramework) -> Result<PipelineOutput> {
+        Ok(PipelineOutput {
+            step: self.name().to_string(),
+            text: framework.think(&input.text).await?,
+        })
+    }
+}
+
+/// Stores the previous output as the answer to the original input, then
+/// passes it through unchanged.
+pub struct LearnStep;
+
+#[async_trait]
+impl PipelineStep for LearnStep {
+    fn name(&self) -> &str {
+        "learn"
+    }
+
+    async fn execute(&self, input: PipelineInput, framework: &mut ACEFramework) -> Result<PipelineOutput> {
+        framework.learn_from_interaction(&input.original, &input.text).await?;
+        Ok(PipelineOutput {
+            step: self.name().to_string(),
+            text: input.text,
+        })
+    }
+}
+
+pub struct SearchStep;
+
+#[async_trait]
+impl PipelineStep for SearchStep {
+    fn name(&self) -> &str {
+        "search"
+    }
+
+    async fn execute(&self, input: PipelineInput, framework: &mut ACEFramework) -> Result<PipelineOutput> {
+        Ok(PipelineOutput {
+            step: self.name().to_string(),
+            text: framework.search_query(&input.text).await.to_display_string(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct PipelineFile {
+    pipeline: PipelineSpec,
+}
+
+#[derive(Deserialize)]
+struct PipelineSpec {
+    steps: Vec<StepSpec>,
+}
+
+#[derive(Deserialize)]
+struct StepSpec {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Default)]
+pub struct AcePipeline {
+    pub steps: Vec<Box<dyn PipelineStep>>,
+}
+
+impl AcePipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn step(mut self, step: impl PipelineStep + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Parses `[[pipeline.steps]]` entries with `type` = query, research,
+    /// think, learn or search.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let file: PipelineFile = toml::from_str(text).map_err(|e| format!("Invalid pipeline: {}", e))?;
+        let mut pipeline = Self::new();
+        for spec in file.pipeline.steps {
+            let step: Box<dyn PipelineStep> = match spec.kind.as_str() {
+                "query" => Box::new(QueryStep),
+                "research" => Box::new(ResearchStep),
+                "think" => Box::new(ThinkStep),
+                "learn" => Box::new(LearnStep),
+                "search" => Box::new(SearchStep),
+                other => return Err(format!("Unknown pipeline step: {}", other)),
+            };
+            pipeline.steps.push(step);
+        }
+        Ok(pipeline)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read pipeline {}: {}", path.display(), e))?;
+        Self::from_toml(&text)
+    }
+
+    /// Runs the steps in order, stopping at the first error.
+    pub async fn run(&self, initial_input: &str, framework: &mut ACEFramework) -> Result<Vec<PipelineOutput>> {
+        let mut outputs = Vec::new();
+        let mut text = initial_input.to_string();
+        for step in &self.steps {
+            let input = PipelineInput {
+                original: initial_input.to_string(),
+                text,
+            };
+            let output = step
+                .execute(input, framework)
+                .await
+                .map_err(|e| format!("Pipeline step '{}' failed: {}", step.name(), e))?;
+            text = output.text.clone();
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+}
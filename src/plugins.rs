@@ -0,0 +1,253 @@
+// ACE Plugins - distributable packages of tools, prompt templates and seeds
+#![allow(dead_code)]
+use crate::functional_core::validate_command_plugins;
+use crate::imperative_shell::load_bullets_jsonl;
+use crate::tools::ResearchTemplate;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+pub const PLUGIN_MANIFEST_FILE: &str = "ace-plugin.toml";
+
+const PROMPTS_DIR: &str = "prompts";
+const SEED_FILE: &str = "seed.jsonl";
+
+/// A plugin tool is an ordinary command plugin; it only runs when
+/// `enable_command_plugins` is set.
+pub type ToolConfig = CommandPlugin;
+
+/// A research template shipped by a plugin, usable as
+/// `/research --template <name>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    /// Relative to the manifest.
+    pub path: PathBuf,
+}
+
+/// Contents of `ace-plugin.toml`. Every path is relative to the manifest and
+/// may not leave the plugin's directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tools: Vec<ToolConfig>,
+    #[serde(default)]
+    pub prompt_templates: Vec<PromptTemplate>,
+    /// JSONL of bullets loaded (and pinned) at startup.
+    #[serde(default)]
+    pub seed_bullets: Option<PathBuf>,
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Only plain relative paths: no root, no drive prefix and no `..`.
+fn sandboxed(path: &Path) -> Result<&Path> {
+    let plain = path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if plain && path.components().next().is_some() {
+        Ok(path)
+    } else {
+        Err(format!("Plugin path '{}' must stay inside the plugin", path.display()))
+    }
+}
+
+impl PluginManifest {
+    pub fn parse(text: &str) -> Result<Self> {
+        let manifest: Self = toml::from_str(text).map_err(|e| format!("Invalid {}: {}", PLUGIN_MANIFEST_FILE, e))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !valid_name(&self.name) {
+            return Err(format!("Invalid plugin name '{}': use letters, digits, '-', '_' and '.'", self.name));
+        }
+        if self.version.trim().is_empty() {
+            return Err(format!("Plugin '{}' has no version", self.name));
+        }
+        validate_command_plugins(&self.tools)?;
+        for template in &self.prompt_templates {
+            if !valid_name(&template.name) {
+                return Err(format!("Invalid prompt template name '{}'", template.name));
+            }
+            sandboxed(&template.path)?;
+        }
+        if let Some(seed) = &self.seed_bullets {
+            sandboxed(seed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a manifest and the files it references are read from.
+enum PluginSource {
+    Dir(PathBuf),
+    Url(String),
+}
+
+impl PluginSource {
+    /// `source` is a URL or path to either the manifest or the directory
+    /// holding it.
+    fn parse(source: &str) -> Self {
+        let is_url = source.starts_with("http://") || source.starts_with("https://");
+        let base = source.strip_suffix(PLUGIN_MANIFEST_FILE).unwrap_or(source);
+        if is_url {
+            Self::Url(base.trim_end_matches('/').to_string())
+        } else {
+            Self::Dir(PathBuf::from(if base.is_empty() { "." } else { base }))
+        }
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        match self {
+            Self::Dir(dir) => {
+                let path = dir.join(path);
+                std::fs::read(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))
+            }
+            Self::Url(base) => {
+                let parts: Vec<String> = path.iter().map(|part| part.to_string_lossy().into_owned()).collect();
+                let url = format!("{}/{}", base, parts.join("/"));
+                let resp = reqwest::get(&url)
+                    .await
+                    .map_err(|e| format!("Download of {} failed: {}", url, e))?;
+                if !resp.status().is_success() {
+                    return Err(format!("Download of {} failed: HTTP {}", url, resp.status()));
+                }
+                resp.bytes()
+                    .await
+                    .map(|bytes| bytes.to_vec())
+                    .map_err(|e| format!("Download of {} failed: {}", url, e))
+            }
+        }
+    }
+}
+
+/// Installed plugins live in `<root>/<name>/`: the manifest, its prompt
+/// templates under `prompts/` and the seed bullets as `seed.jsonl`.
+pub struct PluginStore {
+    pub root: PathBuf,
+}
+
+impl Default for PluginStore {
+    fn default() -> Self {
+        let root = match (std::env::var_os("ACE_PLUGIN_DIR"), std::env::var_os("HOME")) {
+            (Some(dir), _) => PathBuf::from(dir),
+            (None, Some(home)) => Path::new(&home).join(".config").join("ace").join("plugins"),
+            (None, None) => PathBuf::from(".ace").join("plugins"),
+        };
+        Self { root }
+    }
+}
+
+impl PluginStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Validates the manifest at `source` and copies the plugin in,
+    /// replacing an installed version. Templates and seeds are checked
+    /// before anything is written, so a bad package leaves no trace.
+    pub async fn install(&self, source: &str) -> Result<PluginManifest> {
+        let source = PluginSource::parse(source);
+        let text = source.read(Path::new(PLUGIN_MANIFEST_FILE)).await?;
+        let mut manifest = PluginManifest::parse(&String::from_utf8_lossy(&text))?;
+
+        let staging = self.root.join(format!(".{}.partial", manifest.name));
+        let _ = std::fs::remove_dir_all(&staging);
+        let staged = self.stage(&source, &mut manifest, &staging).await;
+        if let Err(e) = staged {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+
+        let dir = self.root.join(&manifest.name);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).map_err(|e| format!("Cannot replace {}: {}", dir.display(), e))?;
+        }
+        std::fs::rename(&staging, &dir).map_err(|e| format!("Cannot install to {}: {}", dir.display(), e))?;
+        Ok(manifest)
+    }
+
+    // Copies every referenced file into `staging` and points the manifest
+    // at the copies.
+    async fn stage(&self, source: &PluginSource, manifest: &mut PluginManifest, staging: &Path) -> Result<()> {
+        let prompts = staging.join(PROMPTS_DIR);
+        std::fs::create_dir_all(&prompts).map_err(|e| format!("Cannot create {}: {}", prompts.display(), e))?;
+
+        for template in &mut manifest.prompt_templates {
+            let bytes = source.read(&template.path).await?;
+            toml::from_str::<ResearchTemplate>(&String::from_utf8_lossy(&bytes))
+                .map_err(|e| format!("Invalid prompt template '{}': {}", template.name, e))?;
+            let relative = Path::new(PROMPTS_DIR).join(format!("{}.toml", template.name));
+            write_file(&staging.join(&relative), &bytes)?;
+            template.path = relative;
+        }
+
+        if let Some(seed) = &manifest.seed_bullets {
+            let bytes = source.read(seed).await?;
+            let path = staging.join(SEED_FILE);
+            write_file(&path, &bytes)?;
+            load_bullets_jsonl(&path)?;
+            manifest.seed_bullets = Some(PathBuf::from(SEED_FILE));
+        }
+
+        let text = toml::to_string(manifest).map_err(|e| format!("Cannot write manifest: {}", e))?;
+        write_file(&staging.join(PLUGIN_MANIFEST_FILE), text.as_bytes())
+    }
+
+    /// Installed plugins by name, each with its directory. Unreadable
+    /// entries are skipped.
+    pub fn list(&self) -> Vec<(PathBuf, PluginManifest)> {
+        let Ok(entries) = std::fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+        let mut plugins: Vec<(PathBuf, PluginManifest)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|dir| dir.file_name().and_then(|n| n.to_str()).is_some_and(valid_name))
+            .filter_map(|dir| {
+                let text = std::fs::read_to_string(dir.join(PLUGIN_MANIFEST_FILE)).ok()?;
+                Some((dir, PluginManifest::parse(&text).ok()?))
+            })
+            .collect();
+        plugins.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        plugins
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        if !valid_name(name) {
+            return Err(format!("Invalid plugin name '{}'", name));
+        }
+        let dir = self.root.join(name);
+        if !dir.join(PLUGIN_MANIFEST_FILE).exists() {
+            return Err(format!("Plugin '{}' is not installed", name));
+        }
+        std::fs::remove_dir_all(&dir).map_err(|e| format!("Cannot remove {}: {}", dir.display(), e))
+    }
+
+    /// The installed copy of the prompt template `name`, from whichever
+    /// plugin ships it first.
+    pub fn find_prompt_template(&self, name: &str) -> Option<PathBuf> {
+        self.list().into_iter().find_map(|(dir, manifest)| {
+            manifest
+                .prompt_templates
+                .iter()
+                .find(|template| template.name == name)
+                .map(|template| dir.join(&template.path))
+        })
+    }
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+    std::fs::write(path, bytes).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
@@ -0,0 +1,148 @@
+// ACE Secrets - API keys kept in the OS keychain instead of ace.toml
+#![allow(dead_code)]
+use crate::imperative_shell::log_warning;
+use crate::types::*;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Keychain service every ACE secret is filed under; the caller's service
+/// name (e.g. `openai`) is the account.
+pub const KEYCHAIN_SERVICE: &str = "ace";
+
+pub trait SecretBackend: Send + Sync {
+    /// `Ok(None)` when no secret is stored for `service`.
+    fn get(&self, service: &str) -> Result<Option<String>>;
+
+    fn set(&self, service: &str, secret: &str) -> Result<()>;
+}
+
+/// The platform keychain through its command-line tool: `security` on
+/// macOS, `secret-tool` (Secret Service) on Linux.
+pub struct SystemKeychain;
+
+fn run_tool(command: &mut Command, stdin: Option<&str>) -> Result<std::process::Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Keychain unavailable: {}", e))?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input.as_bytes())
+            .map_err(|e| format!("Keychain write failed: {}", e))?;
+    }
+    child.wait_with_output().map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+impl SecretBackend for SystemKeychain {
+    fn get(&self, service: &str) -> Result<Option<String>> {
+        let mut command = if cfg!(target_os = "macos") {
+            let mut command = Command::new("security");
+            command.args(["find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", service, "-w"]);
+            command
+        } else if cfg!(target_os = "linux") {
+            let mut command = Command::new("secret-tool");
+            command.args(["lookup", "service", KEYCHAIN_SERVICE, "account", service]);
+            command
+        } else {
+            return Err("System keychain is not supported on this platform".to_string());
+        };
+        let output = run_tool(&mut command, None)?;
+        // Both tools exit non-zero when nothing matches
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let secret = String::from_utf8_lossy(&output.stdout).trim_end_matches('\n').to_string();
+        Ok(Some(secret).filter(|s| !s.is_empty()))
+    }
+
+    fn set(&self, service: &str, secret: &str) -> Result<()> {
+        let output = if cfg!(target_os = "macos") {
+            // -U updates an existing entry instead of failing
+            run_tool(
+                Command::new("security").args([
+                    "add-generic-password",
+                    "-U",
+                    "-s",
+                    KEYCHAIN_SERVICE,
+                    "-a",
+                    service,
+                    "-w",
+                    secret,
+                ]),
+                None,
+            )?
+        } else if cfg!(target_os = "linux") {
+            let label = format!("ACE {} API key", service);
+            run_tool(
+                Command::new("secret-tool").args([
+                    "store",
+                    "--label",
+                    &label,
+                    "service",
+                    KEYCHAIN_SERVICE,
+                    "account",
+                    service,
+                ]),
+                Some(secret),
+            )?
+        } else {
+            return Err("System keychain is not supported on this platform".to_string());
+        };
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Keychain write failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+pub struct SecretStore {
+    backend: Box<dyn SecretBackend>,
+}
+
+impl Default for SecretStore {
+    fn default() -> Self {
+        Self::with_backend(Box::new(SystemKeychain))
+    }
+}
+
+impl SecretStore {
+    pub fn with_backend(backend: Box<dyn SecretBackend>) -> Self {
+        Self { backend }
+    }
+
+    pub fn get_api_key(&self, service: &str) -> Result<String> {
+        self.backend
+            .get(service)?
+            .ok_or_else(|| format!("No API key stored for '{}'", service))
+    }
+
+    pub fn set_api_key(&self, service: &str, key: &str) -> Result<()> {
+        if key.is_empty() {
+            return Err("API key must not be empty".to_string());
+        }
+        self.backend.set(service, key)
+    }
+}
+
+/// Keychain first; the plaintext `[api_keys]` table in ace.toml is only a
+/// fallback and earns a warning.
+pub fn resolve_api_key(config: &AppConfig, secrets: &SecretStore, service: &str) -> Result<String> {
+    match secrets.get_api_key(service) {
+        Ok(key) => Ok(key),
+        Err(keychain_error) => match config.api_keys.get(service) {
+            Some(key) => {
+                log_warning(&format!(
+                    "Using plaintext API key for '{}' from ace.toml; move it with `ace credentials set --service {} --key <key>`",
+                    service, service
+                ));
+                Ok(key.clone())
+            }
+            None => Err(keychain_error),
+        },
+    }
+}
@@ -0,0 +1,122 @@
+// ACE Testing - Deterministic backends for tests
+#![allow(dead_code)]
+use crate::functional_core::create_bullet;
+use crate::imperative_shell::{LlmBackend, TextStream};
+use crate::secrets::SecretBackend;
+use crate::types::*;
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use futures::StreamExt;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Serves scripted responses in order and records every prompt it receives.
+/// Running out of responses panics, so tests fail loudly on unexpected calls.
+#[derive(Default)]
+pub struct MockLlmBackend {
+    pub responses: Mutex<VecDeque<Result<String>>>,
+    pub calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockLlmBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expect(self, response: impl Into<String>) -> Self {
+        self.responses.lock().unwrap().push_back(Ok(response.into()));
+        self
+    }
+
+    pub fn expect_err(self, error: impl Into<String>) -> Self {
+        self.responses.lock().unwrap().push_back(Err(error.into()));
+        self
+    }
+
+    pub fn called_with(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn next_response(&self, prompt: &str) -> Result<String> {
+        self.calls.lock().unwrap().push(prompt.to_string());
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("unexpected call: {}", prompt))
+    }
+}
+
+#[async_trait]
+impl LlmBackend for MockLlmBackend {
+    async fn initialize(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn generate_with_options(&self, prompt: &str, _options: &GenerationOptions) -> Result<String> {
+        self.next_response(prompt)
+    }
+
+    async fn generate_stream_with_options(
+        &self,
+        prompt: &str,
+        _options: &GenerationOptions,
+    ) -> Result<TextStream> {
+        let response = self.next_response(prompt)?;
+        Ok(futures::stream::iter(vec![Ok(response)]).boxed())
+    }
+}
+
+/// Prerecorded exchanges served back in order by a `MockLlmBackend`.
+pub struct ReplayMode {
+    pub recordings: VecDeque<Recording>,
+}
+
+impl ReplayMode {
+    pub fn new(recordings: Vec<Recording>) -> Self {
+        Self {
+            recordings: recordings.into(),
+        }
+    }
+
+    pub fn into_backend(self) -> MockLlmBackend {
+        self.recordings
+            .into_iter()
+            .fold(MockLlmBackend::new(), |mock, recording| mock.expect(recording.response))
+    }
+}
+
+/// Keychain stand-in that keeps secrets in memory.
+#[derive(Default)]
+pub struct InMemorySecretBackend {
+    pub secrets: Mutex<HashMap<String, String>>,
+}
+
+impl SecretBackend for InMemorySecretBackend {
+    fn get(&self, service: &str) -> Result<Option<String>> {
+        Ok(self.secrets.lock().unwrap().get(service).cloned())
+    }
+
+    fn set(&self, service: &str, secret: &str) -> Result<()> {
+        self.secrets.lock().unwrap().insert(service.to_string(), secret.to_string());
+        Ok(())
+    }
+}
+
+/// A bullet with a fixed id, feedback and creation time (`minute` minutes
+/// into 2024), so fixtures render the same on every run.
+pub fn fixture_bullet(id: &str, content: &str, tags: &[&str], helpful: i32, minute: i64) -> ContextBullet {
+    ContextBullet {
+        id: id.to_string(),
+        helpful_count: helpful,
+        created_at: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + chrono::Duration::minutes(minute),
+        ..create_bullet(content.to_string(), tags.iter().map(|t| t.to_string()).collect())
+    }
+}
+
+pub fn fixture_context(bullets: Vec<ContextBullet>) -> ContextState {
+    ContextState {
+        bullets: bullets.into_iter().map(|b| (b.id.clone(), b)).collect(),
+        version: VectorClock::default(),
+    }
+}
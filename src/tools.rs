@@ -1,12 +1,24 @@
 // ACE Tools - Thinking, Search, Deep Research
-use crate::imperative_shell::OllamaClient;
+#![allow(dead_code)]
+use crate::functional_core::{
+    bullets_created_since, correct_query, detect_dependencies, extract_quoted_phrases, jaccard_similarity, parse_critique_score,
+    parse_search_query, tokenize_terms, topological_order, InvertedIndex, ENGLISH_STOP_WORDS,
+};
+use crate::imperative_shell::{log_debug, log_warning, LlmBackend};
 use crate::types::*;
-use std::collections::HashMap;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 
 pub struct ThinkingTool;
 
 impl ThinkingTool {
-    pub async fn think(&self, query: &str, client: &OllamaClient) -> Result<String> {
+    pub async fn think(&self, query: &str, client: &dyn LlmBackend) -> Result<String> {
         let prompt = format!(
             "Think deeply about this query step by step:\n\nQuery: {}\n\nProvide detailed reasoning:\n1. Break down the problem\n2. Consider multiple approaches\n3. Analyze pros and cons\n4. Reach conclusion\n\nThinking process:",
             query
@@ -15,207 +27,1258 @@ impl ThinkingTool {
     }
 }
 
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    /// `Err` when the backend could not be reached, as opposed to `Ok(vec![])`
+    /// for a search with no hits.
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SafeSearch {
+    Off,
+    /// DuckDuckGo's own default, so no parameter is sent.
+    #[default]
+    Moderate,
+    Strict,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DdgSearchConfig {
+    /// DuckDuckGo region code, e.g. `us-en` or `vn-vi`.
+    pub region: Option<String>,
+    pub safe_search: SafeSearch,
+    pub no_redirect: bool,
+}
+
+#[derive(Default)]
+pub struct DuckDuckGoBackend {
+    config: DdgSearchConfig,
+}
+
+impl DuckDuckGoBackend {
+    pub fn new(config: DdgSearchConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn request_url(&self, query: &str) -> String {
+        let mut url = format!(
+            "https://api.duckduckgo.com/?q={}&format=json&no_html=1&skip_disambig=1",
+            urlencoding::encode(query)
+        );
+        if let Some(region) = &self.config.region {
+            url.push_str(&format!("&kl={}", urlencoding::encode(region)));
+        }
+        // `kp` is DuckDuckGo's safe-search parameter
+        match self.config.safe_search {
+            SafeSearch::Off => url.push_str("&kp=-2"),
+            SafeSearch::Moderate => {}
+            SafeSearch::Strict => url.push_str("&kp=1"),
+        }
+        if self.config.no_redirect {
+            url.push_str("&no_redirect=1");
+        }
+        url
+    }
+}
+
+#[async_trait]
+impl SearchBackend for DuckDuckGoBackend {
+    async fn search(&self, query: &str) -> Result<Vec<SearchResult>> {
+        let url = self.request_url(query);
+        let resp = reqwest::get(&url)
+            .await
+            .map_err(|e| format!("Web search request failed: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Web search failed: {}", resp.status()));
+        }
+        let data = resp
+            .json::<serde_json::Value>()
+            .await
+            .map_err(|e| format!("Invalid web search response: {}", e))?;
+
+        let mut results = Vec::new();
+        
+        if let Some(abstract_text) = data["Abstract"].as_str() {
+            if !abstract_text.is_empty() {
+                results.push(SearchResult {
+                    content: abstract_text.to_string(),
+                    relevance: 10.0,
+                    tags: vec![],
+                    source: "web".to_string(),
+                    url: data["AbstractURL"].as_str().map(|s| s.to_string()),
+                    bullet_id: None,
+                });
+            }
+        }
+        
+        if let Some(topics) = data["RelatedTopics"].as_array() {
+            for topic in topics.iter().take(3) {
+                if let Some(text) = topic["Text"].as_str() {
+                    results.push(SearchResult {
+                        content: text.to_string(),
+                        relevance: 5.0,
+                        tags: vec![],
+                        source: "web".to_string(),
+                        url: topic["FirstURL"].as_str().map(|s| s.to_string()),
+                        bullet_id: None,
+                    });
+                }
+            }
+        }
+        
+        Ok(results)
+    }
+}
+
+/// Stands in for the web in dry-run mode: every search succeeds with no hits.
+pub struct DryRunSearchBackend;
+
+#[async_trait]
+impl SearchBackend for DryRunSearchBackend {
+    async fn search(&self, _query: &str) -> Result<Vec<SearchResult>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Predicate over a bullet's metadata, e.g. `|meta| meta["reviewed"] == true`.
+pub type MetadataFilter = Box<dyn Fn(&serde_json::Value) -> bool>;
+
+/// Added to the BM25 score of bullets containing every quoted phrase.
+pub const PHRASE_MATCH_BONUS: f64 = 5.0;
+
+/// Results shown per search page.
+pub const SEARCH_PAGE_SIZE: usize = 5;
+
+/// Jaccard overlap above which a web result repeats a context result.
+pub const SEARCH_DEDUP_THRESHOLD: f64 = 0.8;
+
+/// Drops web results that are at least `similarity_threshold` similar to a
+/// context result, adding the web relevance to that context result instead.
+pub fn dedup_search_results(results: Vec<SearchResult>, similarity_threshold: f64) -> Vec<SearchResult> {
+    let (mut kept, web): (Vec<SearchResult>, Vec<SearchResult>) =
+        results.into_iter().partition(|r| r.source != "web");
+    let context_count = kept.len();
+    for result in web {
+        let duplicate = kept[..context_count]
+            .iter_mut()
+            .find(|c| jaccard_similarity(&c.content, &result.content) >= similarity_threshold);
+        match duplicate {
+            Some(context_result) => context_result.relevance += result.relevance,
+            None => kept.push(result),
+        }
+    }
+    kept
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchConfig {
+    /// Web searches allowed in flight at once per `SearchTool`.
+    pub max_concurrent_searches: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_searches: 3,
+        }
+    }
+}
+
 pub struct SearchTool {
     pub enable_web_search: bool,
+    pub backend: Arc<dyn SearchBackend>,
+    /// Corpus-derived stop-words; `None` uses the built-in English list.
+    pub stop_words: Option<HashSet<String>>,
+    pub config: SearchConfig,
+    web_permits: Arc<tokio::sync::Semaphore>,
 }
 
 impl SearchTool {
     pub fn new(enable_web_search: bool) -> Self {
-        Self { enable_web_search }
+        Self::with_backend(enable_web_search, Arc::new(DuckDuckGoBackend::default()))
     }
 
-    pub fn search_context(&self, query: &str, bullets: &HashMap<String, ContextBullet>) -> Vec<SearchResult> {
-        let query_words: std::collections::HashSet<String> = query
-            .to_lowercase()
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
+    pub fn with_backend(enable_web_search: bool, backend: Arc<dyn SearchBackend>) -> Self {
+        Self {
+            enable_web_search,
+            backend,
+            stop_words: None,
+            config: SearchConfig::default(),
+            web_permits: Arc::new(tokio::sync::Semaphore::new(SearchConfig::default().max_concurrent_searches)),
+        }
+    }
 
-        let mut results: Vec<SearchResult> = bullets
-            .values()
-            .filter_map(|bullet| {
-                let bullet_words: std::collections::HashSet<String> = bullet
-                    .content
-                    .to_lowercase()
-                    .split_whitespace()
-                    .map(|s| s.to_string())
+    pub fn with_config(mut self, config: SearchConfig) -> Self {
+        self.web_permits = Arc::new(tokio::sync::Semaphore::new(config.max_concurrent_searches.max(1)));
+        self.config = config;
+        self
+    }
+
+    pub fn with_stop_words(mut self, stop_words: HashSet<String>) -> Self {
+        self.stop_words = Some(stop_words);
+        self
+    }
+
+    /// `metadata_filter` sees each bullet's metadata as a JSON object and
+    /// drops bullets it rejects before ranking.
+    pub fn search_context(
+        &self,
+        query: &str,
+        bullets: &HashMap<String, ContextBullet>,
+        metadata_filter: Option<MetadataFilter>,
+    ) -> Vec<SearchResult> {
+        match metadata_filter {
+            Some(filter) => {
+                let matching: HashMap<String, ContextBullet> = bullets
+                    .iter()
+                    .filter(|(_, bullet)| filter(&bullet.metadata_value()))
+                    .map(|(id, bullet)| (id.clone(), bullet.clone()))
                     .collect();
+                self.search_context_page(query, &matching, None).results
+            }
+            None => self.search_context_page(query, bullets, None).results,
+        }
+    }
 
-                let overlap = query_words.intersection(&bullet_words).count();
-                if overlap > 0 {
-                    Some(SearchResult {
-                        content: bullet.content.clone(),
-                        relevance: overlap,
-                        tags: bullet.tags.clone(),
-                        source: "context".to_string(),
-                        url: None,
-                    })
-                } else {
-                    None
+    /// `search_context` over bullets created at or after `since`.
+    pub fn search_context_since(
+        &self,
+        query: &str,
+        since: DateTime<Utc>,
+        bullets: &HashMap<String, ContextBullet>,
+    ) -> Vec<SearchResult> {
+        self.search_context_page(query, &bullets_created_since(bullets, since), None).results
+    }
+
+    /// Top results plus tag counts over every match. `tag` restricts the
+    /// search to bullets carrying that tag.
+    pub fn search_context_page(
+        &self,
+        query: &str,
+        bullets: &HashMap<String, ContextBullet>,
+        tag: Option<&str>,
+    ) -> SearchPage {
+        let matches = match tag {
+            Some(tag) => {
+                let tagged: HashMap<String, ContextBullet> = bullets
+                    .iter()
+                    .filter(|(_, bullet)| bullet.tags.iter().any(|t| t == tag))
+                    .map(|(id, bullet)| (id.clone(), bullet.clone()))
+                    .collect();
+                self.rank_context(query, &tagged)
+            }
+            None => self.rank_context(query, bullets),
+        };
+
+        let mut facets = SearchFacets::default();
+        for result in &matches {
+            for tag in &result.tags {
+                *facets.by_tag.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+
+        SearchPage::first_page(matches, facets, None)
+    }
+
+    fn rank_context(&self, query: &str, bullets: &HashMap<String, ContextBullet>) -> Vec<SearchResult> {
+        let boolean_query = parse_search_query(query);
+        let filtered: HashMap<String, ContextBullet>;
+        let (bullets, mut terms) = match &boolean_query {
+            Some(boolean) => {
+                filtered = bullets
+                    .iter()
+                    .filter(|(_, bullet)| boolean.evaluate(bullet))
+                    .map(|(id, bullet)| (id.clone(), bullet.clone()))
+                    .collect();
+                (&filtered, boolean.positive_terms())
+            }
+            None => (bullets, tokenize_terms(query)),
+        };
+
+        let index = InvertedIndex::build(bullets, self.stop_words.as_ref());
+        if boolean_query.is_none() && terms.iter().any(|t| !index.contains(t)) {
+            terms = self.corrected_terms(&terms, &index);
+        }
+        let mut scores = index.bm25(&terms);
+        if boolean_query.is_some() {
+            // Every bullet that satisfies the expression is a hit, even with no positive terms.
+            for id in bullets.keys() {
+                scores.entry(id.clone()).or_insert(0.0);
+            }
+        }
+
+        let phrases = extract_quoted_phrases(query);
+        if !phrases.is_empty() {
+            scores.retain(|id, score| {
+                let content = bullets[id].content.to_lowercase();
+                let matched = phrases.iter().all(|phrase| content.contains(phrase.as_str()));
+                if matched {
+                    *score += PHRASE_MATCH_BONUS;
                 }
+                matched
+            });
+        }
+
+        let mut results: Vec<SearchResult> = scores
+            .into_iter()
+            .filter_map(|(id, score)| bullets.get(&id).map(|bullet| (bullet, score)))
+            .map(|(bullet, score)| SearchResult {
+                content: bullet.content.clone(),
+                relevance: score,
+                tags: bullet.tags.clone(),
+                source: "context".to_string(),
+                url: None,
+                bullet_id: Some(bullet.id.clone()),
             })
             .collect();
 
-        results.sort_by(|a, b| b.relevance.cmp(&a.relevance));
-        results.into_iter().take(5).collect()
+        results.sort_by(|a, b| b.relevance.partial_cmp(&a.relevance).unwrap());
+        results
+    }
+
+    // Terms with no BM25 matches get spell-corrected against the index;
+    // stop-words are passed through untouched.
+    fn corrected_terms(&self, terms: &[String], index: &InvertedIndex) -> Vec<String> {
+        let known_tokens = index.tokens();
+        terms
+            .iter()
+            .map(|term| {
+                let is_stop_word = match &self.stop_words {
+                    Some(words) => words.contains(term),
+                    None => ENGLISH_STOP_WORDS.contains(&term.as_str()),
+                };
+                if is_stop_word || index.contains(term) {
+                    return term.clone();
+                }
+                let corrected = correct_query(term, &known_tokens);
+                if corrected != *term {
+                    log_debug(&format!("Corrected '{}' to '{}'", term, corrected));
+                }
+                corrected
+            })
+            .collect()
     }
 
-    pub async fn search_web(&self, query: &str) -> Vec<SearchResult> {
+    pub async fn search_web(&self, query: &str) -> Result<Vec<SearchResult>> {
         if !self.enable_web_search {
-            return vec![];
+            return Ok(vec![]);
         }
+        let _permit = self.web_permits.acquire().await.map_err(|e| e.to_string())?;
+        self.backend.search(query).await
+    }
 
-        let url = format!("https://api.duckduckgo.com/?q={}&format=json&no_html=1&skip_disambig=1", 
-            urlencoding::encode(query));
-        
-        match reqwest::get(&url).await {
-            Ok(resp) if resp.status().is_success() => {
-                if let Ok(data) = resp.json::<serde_json::Value>().await {
-                    let mut results = Vec::new();
-                    
-                    if let Some(abstract_text) = data["Abstract"].as_str() {
-                        if !abstract_text.is_empty() {
-                            results.push(SearchResult {
-                                content: abstract_text.to_string(),
-                                relevance: 10,
-                                tags: vec![],
-                                source: "web".to_string(),
-                                url: data["AbstractURL"].as_str().map(|s| s.to_string()),
-                            });
-                        }
-                    }
-                    
-                    if let Some(topics) = data["RelatedTopics"].as_array() {
-                        for topic in topics.iter().take(3) {
-                            if let Some(text) = topic["Text"].as_str() {
-                                results.push(SearchResult {
-                                    content: text.to_string(),
-                                    relevance: 5,
-                                    tags: vec![],
-                                    source: "web".to_string(),
-                                    url: topic["FirstURL"].as_str().map(|s| s.to_string()),
-                                });
-                            }
-                        }
-                    }
-                    
-                    return results;
-                }
-            }
-            _ => {}
-        }
-        vec![]
+    /// Runs `search` for every query concurrently; at most
+    /// `max_concurrent_searches` web requests are in flight at a time.
+    pub async fn search_batch(&self, queries: &[&str], bullets: &HashMap<String, ContextBullet>) -> Vec<Vec<SearchResult>> {
+        futures::future::join_all(queries.iter().map(|query| self.search(query, bullets))).await
     }
 
     pub async fn search(&self, query: &str, bullets: &HashMap<String, ContextBullet>) -> Vec<SearchResult> {
-        let mut context_results = self.search_context(query, bullets);
-        let web_results = self.search_web(query).await;
-        
-        context_results.extend(web_results);
-        context_results.sort_by(|a, b| b.relevance.cmp(&a.relevance));
-        context_results.into_iter().take(5).collect()
+        self.search_with_fallback(query, bullets).await.results
+    }
+
+    /// Context and web results combined. A failing web backend is logged and
+    /// recorded in the page's `report`; the context results are still returned.
+    pub async fn search_with_fallback(&self, query: &str, bullets: &HashMap<String, ContextBullet>) -> SearchPage {
+        let context_page = self.search_context_page(query, bullets, None);
+        let mut results = context_page.results;
+        let mut report = SearchReport {
+            context_results: results.len(),
+            ..SearchReport::default()
+        };
+
+        match self.search_web(query).await {
+            Ok(web_results) => {
+                report.web_results = web_results.len();
+                results.extend(web_results);
+                results = dedup_search_results(results, SEARCH_DEDUP_THRESHOLD);
+            }
+            Err(e) => {
+                log_warning(&format!("{}; showing context results only", e));
+                report.web_error = Some(e);
+            }
+        }
+
+        results.sort_by(|a, b| b.relevance.partial_cmp(&a.relevance).unwrap());
+        // Context matches past the first page still count towards the total
+        let unseen_context = context_page.total_count - report.context_results;
+        let mut page = SearchPage::first_page(results, context_page.facets, Some(report));
+        page.total_count += unseen_context;
+        page.next_cursor = (page.total_count > page.results.len()).then(|| page.results.len().to_string());
+        page
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct SearchResult {
     pub content: String,
-    pub relevance: usize,
+    pub relevance: f64,
     pub tags: Vec<String>,
     pub source: String,
     pub url: Option<String>,
+    /// The matching bullet for context results.
+    pub bullet_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchFacets {
+    pub by_tag: HashMap<String, usize>,
+}
+
+impl SearchFacets {
+    /// Tag counts, most frequent first.
+    pub fn sorted_tags(&self) -> Vec<(&String, usize)> {
+        let mut tags: Vec<(&String, usize)> = self.by_tag.iter().map(|(t, c)| (t, *c)).collect();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        tags
+    }
+}
+
+/// Where a combined search's results came from, and why the web part failed.
+#[derive(Debug, Clone, Default)]
+pub struct SearchReport {
+    pub context_results: usize,
+    pub web_results: usize,
+    pub web_error: Option<String>,
+}
+
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    /// Matches found, including those past this page.
+    pub total_count: usize,
+    /// Offset of the next page when there are more matches than shown.
+    pub next_cursor: Option<String>,
+    pub facets: SearchFacets,
+    /// Set by `search_with_fallback`; context-only searches leave it `None`.
+    pub report: Option<SearchReport>,
+}
+
+impl SearchPage {
+    /// The first `SEARCH_PAGE_SIZE` of `matches`, which must be ranked.
+    fn first_page(matches: Vec<SearchResult>, facets: SearchFacets, report: Option<SearchReport>) -> Self {
+        let total_count = matches.len();
+        let results: Vec<SearchResult> = matches.into_iter().take(SEARCH_PAGE_SIZE).collect();
+        let next_cursor = (total_count > results.len()).then(|| results.len().to_string());
+        Self {
+            results,
+            total_count,
+            next_cursor,
+            facets,
+            report,
+        }
+    }
+
+    fn web_unavailable(&self) -> bool {
+        self.report.as_ref().is_some_and(|report| report.web_error.is_some())
+    }
+
+    /// Numbered results for the terminal, with a note when the web part of
+    /// the search failed.
+    pub fn to_display_string(&self) -> String {
+        if self.results.is_empty() {
+            return if self.web_unavailable() {
+                "No results found (web search unavailable).".to_string()
+            } else {
+                "No results found.".to_string()
+            };
+        }
+
+        let mut output = String::new();
+        if self.web_unavailable() {
+            output.push_str(&format!(
+                "{} Web search unavailable, showing learned context only\n",
+                icon(IconKey::Warning)
+            ));
+        }
+        for (i, r) in self.results.iter().enumerate() {
+            let source = if r.source == "web" { IconKey::Web } else { IconKey::Context };
+            output.push_str(&format!(
+                "{}. {} {}...\n",
+                i + 1,
+                icon(source),
+                &r.content.chars().take(100).collect::<String>()
+            ));
+            if let Some(url) = &r.url {
+                output.push_str(&format!("   {} {}\n", icon(IconKey::Link), url));
+            }
+        }
+        if self.total_count > self.results.len() {
+            output.push_str(&format!("({} of {} matches)\n", self.results.len(), self.total_count));
+        }
+        output
+    }
+
+    /// Per-tag breakdown of every match, most frequent tag first.
+    pub fn facets_display_string(&self) -> String {
+        if self.facets.by_tag.is_empty() {
+            return String::new();
+        }
+        let mut output = format!("\n{} By tag:\n", icon(IconKey::Stats));
+        for (tag, count) in self.facets.sorted_tags() {
+            output.push_str(&format!("   {}: {}\n", tag, count));
+        }
+        output.push_str("   Refine with /search --tag <tag> <query>\n");
+        output
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResearchSection {
+    pub name: String,
+    /// Instruction for this section; `{topic}` is replaced with the research topic.
+    pub prompt_template: String,
+    pub max_words: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResearchTemplate {
+    pub sections: Vec<ResearchSection>,
+}
+
+impl ResearchTemplate {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read template {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid template {}: {}", path.display(), e))
+    }
+
+    /// Loads every `*.toml` file in `dir`, keyed by file stem.
+    pub fn load_dir(dir: &Path) -> Result<HashMap<String, ResearchTemplate>> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+
+        let mut templates = HashMap::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                templates.insert(name.to_string(), Self::load(&path)?);
+            }
+        }
+        Ok(templates)
+    }
+}
+
+impl Default for ResearchTemplate {
+    fn default() -> Self {
+        let section = |name: &str, prompt_template: &str, max_words| ResearchSection {
+            name: name.to_string(),
+            prompt_template: prompt_template.to_string(),
+            max_words,
+        };
+
+        Self {
+            sections: vec![
+                section("Executive Summary", "Write an executive summary of the research on {topic}.", 150),
+                section("Key Findings", "List the key findings about {topic}.", 250),
+                section("Detailed Analysis", "Provide a detailed analysis of {topic}.", 400),
+                section("Conclusion", "Write a conclusion for the research on {topic}.", 150),
+            ],
+        }
+    }
+}
+
+/// Builds the prompt used to write one report section.
+pub fn build_section_prompt(topic: &str, sources_text: &str, findings: &str, section: &ResearchSection) -> String {
+    format!(
+        "Research topic: {}\n\nSources consulted:\n{}\n\nResearch findings:\n{}\n\n{} Use at most {} words.\n\n{}:",
+        topic,
+        sources_text,
+        findings,
+        section.prompt_template.replace("{topic}", topic),
+        section.max_words,
+        section.name
+    )
+}
+
+pub struct ReportSection {
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Citation {
+    pub url: String,
+    pub title: String,
+    pub accessed_at: DateTime<Utc>,
+    pub excerpt: String,
+}
+
+/// Returns the 1-based citation number for `result`, adding it if the URL is new.
+fn cite(citations: &mut Vec<Citation>, url: &str, result: &SearchResult) -> usize {
+    if let Some(pos) = citations.iter().position(|c| c.url == url) {
+        return pos + 1;
+    }
+    citations.push(Citation {
+        url: url.to_string(),
+        title: result.content.chars().take(60).collect(),
+        accessed_at: Utc::now(),
+        excerpt: result.content.chars().take(150).collect(),
+    });
+    citations.len()
+}
+
+pub struct ResearchReport {
+    pub topic: String,
+    pub progress: Vec<String>,
+    pub sections: Vec<ReportSection>,
+    pub citations: Vec<Citation>,
+    pub nodes: Vec<ResearchNode>,
+    pub dependencies: QuestionDependencyGraph,
+    pub quality_score: Option<f64>,
+    pub refinement_count: u32,
+}
+
+fn sections_markdown(sections: &[ReportSection]) -> String {
+    sections
+        .iter()
+        .map(|s| format!("\n## {}\n\n{}\n", s.name, s.content))
+        .collect()
+}
+
+impl ResearchReport {
+    pub fn to_markdown(&self) -> String {
+        let mut output = format!("# {}\n", self.topic);
+        output.push_str(&sections_markdown(&self.sections));
+        if !self.citations.is_empty() {
+            output.push_str("\n## References\n\n");
+            for (i, c) in self.citations.iter().enumerate() {
+                output.push_str(&format!(
+                    "{}. [{}]({}) - accessed {}\n",
+                    i + 1,
+                    c.title,
+                    c.url,
+                    c.accessed_at.format("%Y-%m-%d")
+                ));
+            }
+        }
+        output
+    }
+
+    pub fn to_display_string(&self) -> String {
+        format!("{}\n{}\n{}", self.progress.join("\n"), "=".repeat(60), self.to_markdown())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ResearchDepth {
+    pub max_questions: usize,
+    pub max_sub_questions: usize,
+    pub max_web_results_per_question: usize,
+}
+
+impl ResearchDepth {
+    pub fn shallow() -> Self {
+        Self {
+            max_questions: 2,
+            max_sub_questions: 0,
+            max_web_results_per_question: 1,
+        }
+    }
+
+    pub fn standard() -> Self {
+        Self {
+            max_questions: 3,
+            max_sub_questions: 0,
+            max_web_results_per_question: 2,
+        }
+    }
+
+    pub fn deep() -> Self {
+        Self {
+            max_questions: 4,
+            max_sub_questions: 2,
+            max_web_results_per_question: 3,
+        }
+    }
+
+    /// Upper bound on LLM calls spent generating and answering questions.
+    pub fn call_budget(&self) -> usize {
+        self.max_questions * (1 + self.max_sub_questions) + 1
+    }
+}
+
+impl Default for ResearchDepth {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// Which generated questions build on which, and the order they were answered in.
+#[derive(Debug, Clone, Default)]
+pub struct QuestionDependencyGraph {
+    pub questions: Vec<String>,
+    /// `(a, b)`: question `b` depends on question `a`.
+    pub edges: Vec<(usize, usize)>,
+    pub order: Vec<usize>,
+}
+
+impl QuestionDependencyGraph {
+    pub fn build(questions: &[String]) -> Self {
+        let edges = detect_dependencies(questions);
+        let order = topological_order(questions.len(), &edges);
+        Self {
+            questions: questions.to_vec(),
+            edges,
+            order,
+        }
+    }
+}
+
+pub struct ResearchNode {
+    pub question: String,
+    pub answer: String,
+    pub children: Vec<ResearchNode>,
+}
+
+fn parse_question_lines(text: &str, max: usize) -> Vec<String> {
+    text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .take(max)
+        .map(|l| l.trim().to_string())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CritiqueConfig {
+    pub threshold: f64,
+    pub max_refinements: u32,
+}
+
+impl Default for CritiqueConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 7.0,
+            max_refinements: 2,
+        }
+    }
+}
+
+/// Reads local files for use in prompts.
+pub struct FileReadTool;
+
+impl FileReadTool {
+    pub const MAX_IMAGE_BYTES: u64 = 10 * 1024 * 1024;
+
+    /// Reads a PNG or JPEG (detected from its magic bytes) as base64.
+    pub fn read_image(&self, path: &Path) -> Result<Base64Image> {
+        use base64::Engine;
+
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?
+            .len();
+        if size > Self::MAX_IMAGE_BYTES {
+            return Err(format!("{} is {} bytes; images are limited to 10 MB", path.display(), size));
+        }
+        let bytes = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let media_type = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            "image/png"
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            "image/jpeg"
+        } else {
+            return Err(format!("{} is not a PNG or JPEG image", path.display()));
+        };
+        Ok(Base64Image {
+            data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+            media_type: media_type.to_string(),
+        })
+    }
+}
+
+/// What `DeepResearchTool::research_with_progress` reports while it runs.
+pub enum ResearchProgressEvent {
+    StepStarted(u32, String),
+    StepCompleted(u32, Duration),
+    SourceFound(SearchResult),
+    QuestionGenerated(String),
+    /// Index of the answered question.
+    AnswerCompleted(usize),
+    SynthesisStarted,
+    Done(ResearchReport),
+    Failed(String),
 }
 
+impl ResearchProgressEvent {
+    /// The progress line shown for this event, if any.
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            ResearchProgressEvent::StepStarted(step, name) => {
+                let key = match step {
+                    1 => IconKey::Search,
+                    2 => IconKey::Reasoning,
+                    3 => IconKey::Insight,
+                    _ => IconKey::Note,
+                };
+                let gap = if *step > 1 { "\n" } else { "" };
+                Some(format!("{}{} Step {}: {}...", gap, icon(key), step, name))
+            }
+            ResearchProgressEvent::SourceFound(result) => {
+                let source_type = if result.source == "web" {
+                    format!("{} Web", icon(IconKey::Web))
+                } else {
+                    format!("{} Context", icon(IconKey::Context))
+                };
+                let preview: String = result.content.chars().take(80).collect();
+                Some(format!("   {}: {}...", source_type, preview))
+            }
+            ResearchProgressEvent::QuestionGenerated(question) => Some(format!("   {} {}", icon(IconKey::Question), question)),
+            ResearchProgressEvent::AnswerCompleted(i) => Some(format!("   {} Answered Q{}", icon(IconKey::Done), i + 1)),
+            ResearchProgressEvent::StepCompleted(..)
+            | ResearchProgressEvent::SynthesisStarted
+            | ResearchProgressEvent::Done(_)
+            | ResearchProgressEvent::Failed(_) => None,
+        }
+    }
+}
+
+/// Collects the report's progress lines and forwards events to an optional
+/// listener. A dropped receiver is ignored.
+struct ResearchProgress {
+    events: Option<mpsc::Sender<ResearchProgressEvent>>,
+    lines: Vec<String>,
+    step: Option<(u32, Instant)>,
+}
+
+impl ResearchProgress {
+    fn new(events: Option<mpsc::Sender<ResearchProgressEvent>>) -> Self {
+        Self {
+            events,
+            lines: Vec::new(),
+            step: None,
+        }
+    }
+
+    async fn emit(&mut self, event: ResearchProgressEvent) {
+        if let Some(line) = event.describe() {
+            self.lines.push(line);
+        }
+        if let Some(events) = &self.events {
+            let _ = events.send(event).await;
+        }
+    }
+
+    /// Detail that only goes into the report's progress lines.
+    fn note(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    async fn start_step(&mut self, step: u32, name: &str) {
+        self.finish_step().await;
+        self.step = Some((step, Instant::now()));
+        self.emit(ResearchProgressEvent::StepStarted(step, name.to_string())).await;
+    }
+
+    async fn finish_step(&mut self) {
+        if let Some((step, started)) = self.step.take() {
+            self.emit(ResearchProgressEvent::StepCompleted(step, started.elapsed())).await;
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct DeepResearchTool {
     pub enable_web_search: bool,
+    pub template: ResearchTemplate,
+    pub depth: ResearchDepth,
+    pub critique: Option<CritiqueConfig>,
+    pub search_backend: Arc<dyn SearchBackend>,
+    pub search_config: SearchConfig,
 }
 
 impl DeepResearchTool {
     pub fn new(enable_web_search: bool) -> Self {
-        Self { enable_web_search }
+        Self {
+            enable_web_search,
+            template: ResearchTemplate::default(),
+            depth: ResearchDepth::default(),
+            critique: None,
+            search_backend: Arc::new(DuckDuckGoBackend::default()),
+            search_config: SearchConfig::default(),
+        }
+    }
+
+    pub fn with_template(mut self, template: ResearchTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: ResearchDepth) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn with_critique(mut self, critique: CritiqueConfig) -> Self {
+        self.critique = Some(critique);
+        self
+    }
+
+    pub fn with_search_backend(mut self, backend: Arc<dyn SearchBackend>) -> Self {
+        self.search_backend = backend;
+        self
+    }
+
+    pub fn with_search_config(mut self, config: SearchConfig) -> Self {
+        self.search_config = config;
+        self
+    }
+
+    async fn answer_question(
+        &self,
+        question: &str,
+        q_results: &[SearchResult],
+        client: &dyn LlmBackend,
+        citations: &mut Vec<Citation>,
+    ) -> Result<String> {
+        let mut info_lines = Vec::new();
+        for r in q_results.iter().take(self.depth.max_web_results_per_question) {
+            let excerpt: String = r.content.chars().take(150).collect();
+            match &r.url {
+                Some(url) => info_lines.push(format!("[{}] {}", cite(citations, url, r), excerpt)),
+                None => info_lines.push(excerpt),
+            }
+        }
+        let context_info = info_lines.join("\n");
+
+        let answer_prompt = format!(
+            "Question: {}\n\nRelevant information:\n{}\n\nProvide detailed answer:",
+            question, context_info
+        );
+        client.generate(&answer_prompt).await
+    }
+
+    /// Splits `question` into sub-questions, answers each, then merges the
+    /// sub-answers. Stops early once `calls_left` is exhausted. `prefetched`
+    /// holds search results for `question` from an earlier batch, if any.
+    #[allow(clippy::too_many_arguments)]
+    async fn research_node(
+        &self,
+        question: &str,
+        prefetched: Option<Vec<SearchResult>>,
+        search_tool: &SearchTool,
+        client: &dyn LlmBackend,
+        bullets: &HashMap<String, ContextBullet>,
+        citations: &mut Vec<Citation>,
+        calls_left: &mut usize,
+    ) -> Result<ResearchNode> {
+        *calls_left -= 1;
+        if self.depth.max_sub_questions == 0 {
+            let results = match prefetched {
+                Some(results) => results,
+                None => search_tool.search(question, bullets).await,
+            };
+            let answer = self.answer_question(question, &results, client, citations).await?;
+            return Ok(ResearchNode {
+                question: question.to_string(),
+                answer,
+                children: Vec::new(),
+            });
+        }
+
+        let sub_prompt = format!(
+            "Question: {}\n\nBreak this question into {} specific sub-questions, one per line:",
+            question, self.depth.max_sub_questions
+        );
+        let sub_questions = parse_question_lines(&client.generate(&sub_prompt).await?, self.depth.max_sub_questions);
+
+        let queries: Vec<&str> = sub_questions.iter().map(String::as_str).collect();
+        let sub_results = search_tool.search_batch(&queries, bullets).await;
+
+        let mut children = Vec::new();
+        for (sub_question, results) in sub_questions.iter().zip(sub_results) {
+            if *calls_left == 0 {
+                break;
+            }
+            *calls_left -= 1;
+            if let Ok(answer) = self.answer_question(sub_question, &results, client, citations).await {
+                children.push(ResearchNode {
+                    question: sub_question.clone(),
+                    answer,
+                    children: Vec::new(),
+                });
+            }
+        }
+
+        let findings = children
+            .iter()
+            .map(|c| format!("- {}: {}", c.question, c.answer))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let answer = if *calls_left == 0 {
+            findings
+        } else {
+            *calls_left -= 1;
+            let merge_prompt = format!(
+                "Question: {}\n\nSub-question findings:\n{}\n\nCombine these findings into a detailed answer:",
+                question, findings
+            );
+            client.generate(&merge_prompt).await?
+        };
+
+        Ok(ResearchNode {
+            question: question.to_string(),
+            answer,
+            children,
+        })
+    }
+
+    async fn write_sections(
+        &self,
+        topic: &str,
+        sources_text: &str,
+        findings: &str,
+        client: &dyn LlmBackend,
+        progress: &mut ResearchProgress,
+    ) -> Result<Vec<ReportSection>> {
+        let mut sections = Vec::new();
+        for section in &self.template.sections {
+            let section_prompt = build_section_prompt(topic, sources_text, findings, section);
+            let content = client.generate(&section_prompt).await?;
+            progress.note(format!("   {} {}", icon(IconKey::Done), section.name));
+            sections.push(ReportSection {
+                name: section.name.clone(),
+                content,
+            });
+        }
+        Ok(sections)
     }
 
     pub async fn research(
         &self,
         topic: &str,
-        client: &OllamaClient,
+        client: &dyn LlmBackend,
         bullets: &HashMap<String, ContextBullet>,
-    ) -> Result<String> {
-        let mut output = Vec::new();
-        
-        output.push("🔍 Step 1: Searching knowledge sources...".to_string());
-        let search_tool = SearchTool::new(self.enable_web_search);
+    ) -> Result<ResearchReport> {
+        self.run(topic, client, bullets, &mut ResearchProgress::new(None)).await
+    }
+
+    /// Runs the research in the background, streaming progress events and
+    /// finishing with `Done` or `Failed`. Dropping the receiver is fine.
+    pub fn research_with_progress(
+        &self,
+        topic: &str,
+        client: Arc<dyn LlmBackend>,
+        bullets: HashMap<String, ContextBullet>,
+    ) -> mpsc::Receiver<ResearchProgressEvent> {
+        let (events, receiver) = mpsc::channel(32);
+        let tool = self.clone();
+        let topic = topic.to_string();
+        tokio::spawn(async move {
+            let mut progress = ResearchProgress::new(Some(events.clone()));
+            let outcome = match tool.run(&topic, client.as_ref(), &bullets, &mut progress).await {
+                Ok(report) => ResearchProgressEvent::Done(report),
+                Err(e) => ResearchProgressEvent::Failed(e),
+            };
+            let _ = events.send(outcome).await;
+        });
+        receiver
+    }
+
+    async fn run(
+        &self,
+        topic: &str,
+        client: &dyn LlmBackend,
+        bullets: &HashMap<String, ContextBullet>,
+        progress: &mut ResearchProgress,
+    ) -> Result<ResearchReport> {
+        progress.start_step(1, "Searching knowledge sources").await;
+        let search_tool =
+            SearchTool::with_backend(self.enable_web_search, self.search_backend.clone()).with_config(self.search_config);
         let existing = search_tool.search(topic, bullets).await;
-        
-        if !existing.is_empty() {
-            output.push(format!("   Found {} relevant sources", existing.len()));
-            for (i, result) in existing.iter().take(3).enumerate() {
-                let source_type = if result.source == "web" { "🌐 Web" } else { "📚 Context" };
-                let preview: String = result.content.chars().take(80).collect();
-                output.push(format!("   {}. {}: {}...", i + 1, source_type, preview));
-            }
+        for result in &existing {
+            progress.emit(ResearchProgressEvent::SourceFound(result.clone())).await;
         }
-        
-        output.push("\n🤔 Step 2: Generating research questions...".to_string());
+
+        progress.start_step(2, "Generating research questions").await;
         let questions_prompt = format!(
-            "Research topic: {}\n\nBased on available information, generate 3 specific research questions to explore:",
-            topic
+            "Research topic: {}\n\nBased on available information, generate {} specific research questions to explore:",
+            topic, self.depth.max_questions
         );
         
+        let mut calls_left = self.depth.call_budget() - 1;
         let questions = client.generate(&questions_prompt).await?;
-        let question_list: Vec<String> = questions
-            .lines()
-            .take(3)
-            .filter(|l| !l.trim().is_empty())
-            .map(|l| l.trim().to_string())
-            .collect();
+        let question_list = parse_question_lines(&questions, self.depth.max_questions);
         
-        for (i, q) in question_list.iter().enumerate() {
-            output.push(format!("   Q{}: {}", i + 1, q));
+        for q in &question_list {
+            progress.emit(ResearchProgressEvent::QuestionGenerated(q.clone())).await;
         }
         
-        output.push("\n💡 Step 3: Researching answers...".to_string());
-        let mut answers = Vec::new();
-        for (i, question) in question_list.iter().enumerate() {
-            let q_results = search_tool.search(question, bullets).await;
-            let context_info: String = q_results
-                .iter()
-                .take(2)
-                .map(|r| r.content.chars().take(150).collect::<String>())
-                .collect::<Vec<_>>()
-                .join("\n");
-            
-            let answer_prompt = format!(
-                "Question: {}\n\nRelevant information:\n{}\n\nProvide detailed answer:",
-                question, context_info
-            );
-            
-            if let Ok(answer) = client.generate(&answer_prompt).await {
-                output.push(format!("   ✓ Answered Q{}", i + 1));
-                answers.push(format!("Q{}: {}\nA{}: {}", i + 1, question, i + 1, answer));
+        let dependencies = QuestionDependencyGraph::build(&question_list);
+        for &(from, to) in &dependencies.edges {
+            progress.note(format!("   Q{} builds on Q{}", to + 1, from + 1));
+        }
+
+        progress.start_step(3, "Researching answers").await;
+        let mut nodes = Vec::new();
+        let mut citations = Vec::new();
+        // Leaf questions only need a search and one LLM call, so their
+        // searches can all run up front.
+        let mut prefetched: Vec<Option<Vec<SearchResult>>> = if self.depth.max_sub_questions == 0 {
+            let queries: Vec<&str> = question_list.iter().map(String::as_str).collect();
+            search_tool.search_batch(&queries, bullets).await.into_iter().map(Some).collect()
+        } else {
+            vec![None; question_list.len()]
+        };
+        for &i in &dependencies.order {
+            let question = &question_list[i];
+            if calls_left == 0 {
+                progress.note(format!(
+                    "   {} LLM call budget reached, skipping remaining questions",
+                    icon(IconKey::Warning)
+                ));
+                break;
+            }
+            let node = self
+                .research_node(question, prefetched[i].take(), &search_tool, client, bullets, &mut citations, &mut calls_left)
+                .await;
+            if let Ok(node) = node {
+                progress.emit(ResearchProgressEvent::AnswerCompleted(i)).await;
+                for j in 0..node.children.len() {
+                    progress.note(format!("      {} Answered Q{}.{}", icon(IconKey::Done), i + 1, j + 1));
+                }
+                nodes.push(node);
             }
         }
+
+        let answers: Vec<String> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| format!("Q{}: {}\nA{}: {}", i + 1, n.question, i + 1, n.answer))
+            .collect();
         
-        output.push("\n📝 Step 4: Synthesizing comprehensive report...\n".to_string());
-        
+        progress.start_step(4, "Writing report sections").await;
+        progress.emit(ResearchProgressEvent::SynthesisStarted).await;
+
         let sources_text: String = existing
             .iter()
             .take(3)
             .map(|e| format!("- {}", e.content.chars().take(200).collect::<String>()))
             .collect::<Vec<_>>()
             .join("\n");
-        
-        let synthesis_prompt = format!(
-            "Research topic: {}\n\nSources consulted:\n{}\n\nResearch findings:\n{}\n\nSynthesize a comprehensive, well-structured report with:\n1. Executive summary\n2. Key findings\n3. Detailed analysis\n4. Conclusion\n\nReport:",
-            topic,
-            sources_text,
-            answers.join("\n")
-        );
-        
-        let synthesis = client.generate(&synthesis_prompt).await?;
-        
-        output.push("=".repeat(60));
-        output.push(synthesis);
-        
-        Ok(output.join("\n"))
+
+        let references_text = if citations.is_empty() {
+            String::new()
+        } else {
+            let list = citations
+                .iter()
+                .enumerate()
+                .map(|(i, c)| format!("[{}] {} ({})", i + 1, c.title, c.url))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!(
+                "\n\nReferences:\n{}\n\nWhere you use a reference, cite it inline with its marker, e.g. [1], [2].",
+                list
+            )
+        };
+
+        let findings = format!("{}{}", answers.join("\n"), references_text);
+        let mut sections = self
+            .write_sections(topic, &sources_text, &findings, client, progress)
+            .await?;
+
+        let mut quality_score = None;
+        let mut refinement_count = 0;
+        if let Some(critique) = &self.critique {
+            loop {
+                let critique_prompt = format!(
+                    "Research report on {}:\n{}\n\nRate this research report from 1-10 and list what's missing.\nFormat:\nSCORE: N\nMISSING: what is missing",
+                    topic,
+                    sections_markdown(&sections)
+                );
+                let review = client.generate(&critique_prompt).await?;
+                quality_score = parse_critique_score(&review);
+
+                match quality_score {
+                    Some(score) if score < critique.threshold && refinement_count < critique.max_refinements => {
+                        refinement_count += 1;
+                        log_debug(&format!(
+                            "Research critique scored {:.1}/10, refining ({}/{})",
+                            score, refinement_count, critique.max_refinements
+                        ));
+                        progress.note(format!("   {} Refining report (score {:.1}/10)", icon(IconKey::Refine), score));
+                        let refined_findings = format!("{}\n\nReviewer critique of the previous draft:\n{}", findings, review);
+                        sections = self
+                            .write_sections(topic, &sources_text, &refined_findings, client, progress)
+                            .await?;
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        progress.finish_step().await;
+        Ok(ResearchReport {
+            topic: topic.to_string(),
+            progress: std::mem::take(&mut progress.lines),
+            sections,
+            citations,
+            nodes,
+            dependencies,
+            quality_score,
+            refinement_count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture_bullet, MockLlmBackend};
+
+    fn corpus(bullets: Vec<ContextBullet>) -> HashMap<String, ContextBullet> {
+        bullets.into_iter().map(|b| (b.id.clone(), b)).collect()
+    }
+
+    #[test]
+    fn exact_phrase_match_ranks_first() {
+        let bullets = corpus(vec![
+            fixture_bullet("phrase", "Context engineering curates what the model sees", &[], 0, 0),
+            fixture_bullet("split-1", "Engineering teams share context in design docs", &[], 0, 1),
+            fixture_bullet("split-2", "Context, context and more context beats engineering effort", &[], 0, 2),
+            fixture_bullet("unrelated", "Run cargo fmt before committing", &[], 0, 3),
+        ]);
+        let tool = SearchTool::with_backend(false, Arc::new(DryRunSearchBackend));
+
+        let unquoted = tool.search_context("context engineering", &bullets, None);
+        assert_eq!(unquoted.len(), 3);
+
+        let quoted = tool.search_context("\"context engineering\"", &bullets, None);
+        assert_eq!(quoted[0].bullet_id.as_deref(), Some("phrase"));
+        assert!(quoted[0].relevance > PHRASE_MATCH_BONUS);
+        assert_eq!(quoted.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn research_synthesis_prompt_snapshot() {
+        let bullets: HashMap<String, ContextBullet> = [fixture_bullet(
+            "rustfmt-1",
+            "cargo fmt formats Rust code using rustfmt",
+            &["rust"],
+            2,
+            0,
+        )]
+        .into_iter()
+        .map(|b| (b.id.clone(), b))
+        .collect();
+        let client = MockLlmBackend::new()
+            .expect("1. What does cargo fmt change?\n2. Where is rustfmt configured?")
+            .expect("It rewrites spacing, indentation and line breaks.")
+            .expect("In rustfmt.toml at the crate root.")
+            .expect("Summary")
+            .expect("Findings")
+            .expect("Analysis")
+            .expect("Conclusion");
+        let tool = DeepResearchTool::new(false)
+            .with_depth(ResearchDepth::shallow())
+            .with_search_backend(Arc::new(DryRunSearchBackend));
+
+        tool.research("cargo fmt", &client, &bullets).await.unwrap();
+
+        let calls = client.called_with();
+        assert_eq!(calls.len(), 7);
+        // Question generation and two answers come first, then one call per section
+        insta::assert_snapshot!(calls[3]);
     }
 }
@@ -0,0 +1,227 @@
+// ACE Transcript - PDF export of a session's exchanges
+#![allow(dead_code)]
+use crate::ace::{ACEFramework, ContextStore};
+use crate::types::*;
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Pt};
+use std::io::BufWriter;
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+
+// US Letter, in points
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const MARGIN: f32 = 72.0;
+const TEXT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.5;
+const HEADING_SIZE: f32 = 16.0;
+const TITLE_SIZE: f32 = 24.0;
+
+const TOP_BULLETS: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Style {
+    // Builtin fonts carry no metrics, so lines are wrapped by character
+    // count using a conservative average glyph width (in em)
+    fn glyph_width(self) -> f32 {
+        match self {
+            Style::Regular => 0.52,
+            Style::Bold => 0.58,
+            Style::Mono => 0.6,
+        }
+    }
+}
+
+/// The builtin fonts only cover Windows-1252 and silently drop anything
+/// else, so accented letters outside it (most of Vietnamese) lose their
+/// marks instead of disappearing.
+fn pdf_text(text: &str) -> String {
+    let encodable = |c: char| (' '..='~').contains(&c) || ('\u{a0}'..='\u{ff}').contains(&c) || "‘’“”–—•…€".contains(c);
+    text.chars()
+        .flat_map(|c| {
+            if encodable(c) {
+                vec![c]
+            } else {
+                let base: Vec<char> = c.to_string().nfd().filter(|d| encodable(*d)).collect();
+                if base.is_empty() {
+                    vec!['?']
+                } else {
+                    base
+                }
+            }
+        })
+        .collect()
+}
+
+/// Word-wraps `text` to `width` characters; words longer than a line are
+/// split. Code keeps its spacing and is only split.
+fn wrap(text: &str, width: usize, style: Style) -> Vec<String> {
+    let chunk = |s: &str| -> Vec<String> {
+        let chars: Vec<char> = s.chars().collect();
+        chars.chunks(width.max(1)).map(|c| c.iter().collect()).collect()
+    };
+    if matches!(style, Style::Mono) {
+        return if text.is_empty() { vec![String::new()] } else { chunk(text) };
+    }
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        for piece in chunk(word) {
+            if !line.is_empty() && line.chars().count() + 1 + piece.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&piece);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Lays text out top to bottom, starting a new page when one fills up.
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    mono: IndirectFontRef,
+    layer: PdfLayerReference,
+    y: f32,
+}
+
+impl PdfWriter {
+    fn new(title: &str) -> Result<Self> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm::from(Pt(PAGE_WIDTH)), Mm::from(Pt(PAGE_HEIGHT)), "Layer 1");
+        let font = |font| doc.add_builtin_font(font).map_err(|e| format!("PDF font error: {}", e));
+        let regular = font(BuiltinFont::Helvetica)?;
+        let bold = font(BuiltinFont::HelveticaBold)?;
+        let mono = font(BuiltinFont::Courier)?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Self {
+            doc,
+            regular,
+            bold,
+            mono,
+            layer,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm::from(Pt(PAGE_WIDTH)), Mm::from(Pt(PAGE_HEIGHT)), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn text(&mut self, text: &str, style: Style, size: f32) {
+        let font = match style {
+            Style::Regular => &self.regular,
+            Style::Bold => &self.bold,
+            Style::Mono => &self.mono,
+        };
+        let font = font.clone();
+        let width = (TEXT_WIDTH / (size * style.glyph_width())) as usize;
+        let leading = size * 1.35;
+        for line in wrap(&pdf_text(text), width, style) {
+            if self.y - leading < MARGIN {
+                self.new_page();
+            }
+            self.y -= leading;
+            self.layer
+                .use_text(line, size, Mm::from(Pt(MARGIN)), Mm::from(Pt(self.y)), &font);
+        }
+    }
+
+    /// Prose with ``` fenced blocks set in the monospace font.
+    fn markdown(&mut self, text: &str, style: Style, size: f32) {
+        let mut in_code = false;
+        for line in text.lines() {
+            if line.trim_start().starts_with("```") {
+                in_code = !in_code;
+                continue;
+            }
+            if in_code {
+                self.text(line, Style::Mono, CODE_SIZE);
+            } else {
+                self.text(line, style, size);
+            }
+        }
+    }
+
+    fn gap(&mut self, points: f32) {
+        self.y -= points;
+    }
+
+    fn save(self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        self.doc
+            .save(&mut BufWriter::new(file))
+            .map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+    }
+}
+
+impl ACEFramework {
+    /// Writes the exchanges recorded this session to a Letter-size PDF: a
+    /// title page, one page per exchange (longer ones run on) and a page of
+    /// the most helpful bullets. `session_id` only labels the document.
+    pub fn export_transcript_pdf(&self, session_id: &str, path: &Path) -> Result<()> {
+        let context = self.curator.get_context();
+        let title = format!("ACE transcript {}", session_id);
+        let mut pdf = PdfWriter::new(&title)?;
+
+        pdf.text("ACE Transcript", Style::Bold, TITLE_SIZE);
+        pdf.gap(BODY_SIZE);
+        let metadata = [
+            ("Session", session_id.to_string()),
+            ("Date", chrono::Utc::now().format("%Y-%m-%d %H:%M UTC").to_string()),
+            ("Model", self.config.model.clone()),
+            ("Bullets", context.bullets.len().to_string()),
+            ("Exchanges", self.recordings.len().to_string()),
+        ];
+        for (label, value) in metadata {
+            pdf.text(&format!("{}: {}", label, value), Style::Regular, BODY_SIZE);
+        }
+
+        for (i, recording) in self.recordings.iter().enumerate() {
+            pdf.new_page();
+            pdf.text(&format!("Exchange {}", i + 1), Style::Bold, HEADING_SIZE);
+            pdf.gap(BODY_SIZE);
+            pdf.markdown(&recording.query, Style::Bold, BODY_SIZE);
+            pdf.gap(BODY_SIZE);
+            pdf.markdown(&recording.response, Style::Regular, BODY_SIZE);
+        }
+
+        pdf.new_page();
+        pdf.text("Most helpful bullets", Style::Bold, HEADING_SIZE);
+        pdf.gap(BODY_SIZE);
+        let mut bullets: Vec<&ContextBullet> = context.bullets.values().collect();
+        bullets.sort_by(|a, b| {
+            (b.helpful_count - b.harmful_count)
+                .cmp(&(a.helpful_count - a.harmful_count))
+                .then_with(|| b.helpful_count.cmp(&a.helpful_count))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        if bullets.is_empty() {
+            pdf.text("No bullets yet.", Style::Regular, BODY_SIZE);
+        }
+        for (i, bullet) in bullets.into_iter().take(TOP_BULLETS).enumerate() {
+            let score = bullet.helpful_count - bullet.harmful_count;
+            pdf.text(&format!("{}. [{:+}] {}", i + 1, score, bullet.id), Style::Bold, BODY_SIZE);
+            pdf.markdown(&bullet.content, Style::Regular, BODY_SIZE);
+            pdf.gap(BODY_SIZE / 2.0);
+        }
+
+        pdf.save(path)
+    }
+}
@@ -2,7 +2,8 @@
 #![allow(dead_code)]
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 
 // Result type for Railway-Oriented Programming
 pub type Result<T> = std::result::Result<T, String>;
@@ -14,8 +15,47 @@ pub struct ContextBullet {
     pub content: String,
     pub helpful_count: i32,
     pub harmful_count: i32,
+    /// Who rated the bullet: per-node counts whose totals are
+    /// `helpful_count` and `harmful_count`, so instances that rated it
+    /// concurrently can be merged without losing or double-counting votes.
+    #[serde(default)]
+    pub helpful_votes: GCounter,
+    #[serde(default)]
+    pub harmful_votes: GCounter,
     pub created_at: DateTime<Utc>,
     pub tags: Vec<String>,
+    /// Ephemeral bullets are ignored by retrieval and purged once this passes.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Pinned bullets are never evicted or archived automatically.
+    #[serde(default)]
+    pub pinned: bool,
+    /// Times the bullet was retrieved for a query.
+    #[serde(default)]
+    pub access_count: u32,
+    /// User annotations such as a source URL or a review-by date.
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
+    /// Previous contents and when they were replaced, oldest first.
+    #[serde(default)]
+    pub content_history: Vec<(String, DateTime<Utc>)>,
+    /// Bumped by every edit and rollback of the content.
+    #[serde(default)]
+    pub revision: u32,
+    /// Fence language of a code bullet (`rust`, `python`, ...), if tagged.
+    #[serde(default)]
+    pub code_language: Option<String>,
+}
+
+impl ContextBullet {
+    /// `metadata` as a JSON object, as seen by `MetadataFilter`s.
+    pub fn metadata_value(&self) -> serde_json::Value {
+        serde_json::Value::Object(self.metadata.clone().into_iter().collect())
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -26,12 +66,15 @@ pub struct ReasoningStep {
 
 #[derive(Debug, Clone)]
 pub struct Trajectory {
+    pub id: String,
     pub query: String,
     pub steps: Vec<ReasoningStep>,
     pub outcome: String,
     pub success: bool,
     pub used_bullets: Vec<String>,
     pub feedback: Option<String>,
+    /// Mean of the judge model's 1-5 scores, when the response was assessed.
+    pub quality_score: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -42,16 +85,586 @@ pub struct Insight {
     pub source_id: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct Claim {
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Contradiction {
+    pub claim: String,
+    pub bullet_id: String,
+    pub bullet_content: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerificationReport {
+    pub claims: Vec<Claim>,
+    pub contradictions: Vec<Contradiction>,
+}
+
+/// A judge model's 1-5 ratings of a response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityAssessment {
+    pub accuracy: u8,
+    pub completeness: u8,
+    pub conciseness: u8,
+}
+
+impl QualityAssessment {
+    pub fn average(&self) -> f64 {
+        (self.accuracy as f64 + self.completeness as f64 + self.conciseness as f64) / 3.0
+    }
+}
+
+/// Responses averaging at least this get the bullets they used pinned.
+pub const QUALITY_PIN_THRESHOLD: f64 = 4.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct VerificationConfig {
+    pub enabled: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct DeltaUpdate {
     pub bullets: Vec<ContextBullet>,
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextState {
     pub bullets: HashMap<String, ContextBullet>,
-    pub version: i32,
+    pub version: VectorClock,
+}
+
+/// Per-node update counts. Each instance only ever increments its own entry,
+/// so clocks from different machines merge by taking the pointwise maximum.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorClock {
+    pub counts: HashMap<String, u64>,
+}
+
+impl VectorClock {
+    /// Records one update made by this instance.
+    pub fn increment(&mut self) {
+        *self.counts.entry(local_node_id().to_string()).or_insert(0) += 1;
+    }
+
+    pub fn merge(&self, other: &VectorClock) -> VectorClock {
+        let mut counts = self.counts.clone();
+        for (node, &count) in &other.counts {
+            let entry = counts.entry(node.clone()).or_insert(0);
+            *entry = (*entry).max(count);
+        }
+        VectorClock { counts }
+    }
+
+    /// Total number of updates seen across all nodes.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+}
+
+/// Grow-only counter: each node increments its own entry, the value is the
+/// sum over nodes and replicas merge by pointwise maximum.
+pub type GCounter = VectorClock;
+
+/// `ACE_NODE_ID` if set, otherwise a random id fixed for the process lifetime.
+pub fn local_node_id() -> &'static str {
+    static NODE_ID: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    NODE_ID.get_or_init(|| std::env::var("ACE_NODE_ID").unwrap_or_else(|_| uuid::Uuid::new_v4().to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageCode {
+    Vietnamese,
+    Cjk,
+    Russian,
+    European,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContextHealth {
+    /// Weighted mean of the components, 0.0-1.0.
+    pub score: f64,
+    /// freshness, diversity, helpfulness, density, coverage - each 0.0-1.0.
+    pub breakdown: HashMap<String, f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CuratorConfig {
+    /// Weight per `ContextHealth` component; missing components count as 0.
+    pub health_weights: HashMap<String, f64>,
+    /// Bullets older than this whose helpful-minus-harmful score is below
+    /// `archive_min_score` are archived automatically. `None` disables it.
+    pub archive_after: Option<chrono::Duration>,
+    pub archive_min_score: i32,
+    /// Lowest-scoring unpinned bullets are evicted past this many. `None`
+    /// keeps every bullet.
+    pub max_bullets: Option<usize>,
+    pub eviction_strategy: EvictionStrategy,
+    /// Previous contents kept per bullet by `edit_bullet`.
+    pub history_depth: usize,
+    /// Summarize bullets by tag once the context passes
+    /// `COMPACTION_THRESHOLD` of `max_bullets`, before eviction has to drop
+    /// them.
+    pub auto_compact: bool,
+    /// Keep Markdown (fences, lists) in bullets sent to the LLM instead of
+    /// stripping it to save tokens.
+    pub rich_context: bool,
+    pub addressing: BulletAddressing,
+    /// Only retrieve bullets (for the query prompt, `/qa`, offline answers
+    /// and verification) that share tags or terms with the topic of the last
+    /// few exchanges, so one subject's bullets stay out of another's.
+    pub topic_scoped: bool,
+}
+
+/// How new bullets get their ids.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BulletAddressing {
+    #[default]
+    Uuid,
+    /// `sha256_hex` of the normalized content: inserting content that is
+    /// already stored is a no-op, and corpora merge by plain map union.
+    ContentHash,
+}
+
+/// Fraction of `max_bullets` at which auto-compaction kicks in.
+pub const COMPACTION_THRESHOLD: f64 = 0.8;
+
+/// Which bullets `max_bullets` eviction removes first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvictionStrategy {
+    /// Lowest helpful-minus-harmful score.
+    LowestScore,
+    /// Fewest retrievals; keeps bullets that are used even without feedback.
+    LeastFrequentlyUsed,
+    OldestFirst,
+    /// Weighted sum of score, retrievals and (negated) age in days.
+    Combined(f64, f64, f64),
+}
+
+impl EvictionStrategy {
+    /// How much a bullet is worth keeping; the lowest are evicted first.
+    pub fn retention(&self, bullet: &ContextBullet, now: DateTime<Utc>) -> f64 {
+        let score = (bullet.helpful_count - bullet.harmful_count) as f64;
+        let frequency = bullet.access_count as f64;
+        let age_days = (now - bullet.created_at).num_seconds() as f64 / 86_400.0;
+        match *self {
+            EvictionStrategy::LowestScore => score,
+            EvictionStrategy::LeastFrequentlyUsed => frequency,
+            EvictionStrategy::OldestFirst => -age_days,
+            EvictionStrategy::Combined(w_score, w_freq, w_age) => {
+                w_score * score + w_freq * frequency - w_age * age_days
+            }
+        }
+    }
+}
+
+impl Default for CuratorConfig {
+    fn default() -> Self {
+        let health_weights = ["freshness", "diversity", "helpfulness", "density", "coverage"]
+            .iter()
+            .map(|name| (name.to_string(), 0.2))
+            .collect();
+        Self {
+            health_weights,
+            archive_after: Some(chrono::Duration::days(30)),
+            archive_min_score: 0,
+            max_bullets: None,
+            eviction_strategy: EvictionStrategy::LowestScore,
+            history_depth: 5,
+            auto_compact: false,
+            rich_context: false,
+            addressing: BulletAddressing::Uuid,
+            topic_scoped: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedBullet {
+    pub bullet: ContextBullet,
+    pub archived_at: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// Estimated token use of each prompt part against the model's context window.
+#[derive(Debug, Clone, Default)]
+pub struct ContextWindowView {
+    pub total_tokens: usize,
+    pub system_prompt_tokens: usize,
+    pub context_bullets_tokens: usize,
+    pub conversation_history_tokens: usize,
+    pub query_tokens: usize,
+    pub remaining_tokens: usize,
+}
+
+impl ContextWindowView {
+    /// `[System:5%][Bullets:30%][History:20%][Query:2%][Free:43%]`
+    pub fn to_bar(&self) -> String {
+        let percent = |tokens: usize| {
+            if self.total_tokens == 0 {
+                0
+            } else {
+                (tokens as f64 / self.total_tokens as f64 * 100.0).round() as usize
+            }
+        };
+        [
+            ("System", self.system_prompt_tokens),
+            ("Bullets", self.context_bullets_tokens),
+            ("History", self.conversation_history_tokens),
+            ("Query", self.query_tokens),
+            ("Free", self.remaining_tokens),
+        ]
+        .iter()
+        .map(|(label, tokens)| format!("[{}:{}%]", label, percent(*tokens)))
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExplainedResponse {
+    pub response: String,
+    /// Bullets from `get_relevant_bullets` with their relevance scores.
+    pub contributing_bullets: Vec<(ContextBullet, f64)>,
+    pub prompt_used: String,
+}
+
+/// One query/response exchange, stored as a JSONL line for replay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub query: String,
+    pub response: String,
+}
+
+/// A context search and which of its bullets the following query used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchEvent {
+    pub timestamp: DateTime<Utc>,
+    pub query: String,
+    pub results_shown: Vec<String>,
+    pub results_used: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchAnalytics {
+    pub log: Vec<SearchEvent>,
+    /// The last event still waits for the next query to mark its used results.
+    awaiting_query: bool,
+}
+
+impl SearchAnalytics {
+    pub fn record(&mut self, query: &str, results_shown: Vec<String>) {
+        self.log.push(SearchEvent {
+            timestamp: Utc::now(),
+            query: query.to_string(),
+            results_shown,
+            results_used: Vec::new(),
+        });
+        self.awaiting_query = true;
+    }
+
+    /// Marks the shown results of the latest search that `used_ids` contains.
+    pub fn mark_used(&mut self, used_ids: &[String]) {
+        if !std::mem::take(&mut self.awaiting_query) {
+            return;
+        }
+        if let Some(event) = self.log.last_mut() {
+            event.results_used = event
+                .results_shown
+                .iter()
+                .filter(|id| used_ids.contains(id))
+                .cloned()
+                .collect();
+        }
+    }
+
+    pub fn recent(&self, count: usize) -> &[SearchEvent] {
+        &self.log[self.log.len().saturating_sub(count)..]
+    }
+
+    /// Share of shown results that were used, over all logged searches.
+    pub fn click_through_rate(&self) -> f64 {
+        let shown: usize = self.log.iter().map(|e| e.results_shown.len()).sum();
+        let used: usize = self.log.iter().map(|e| e.results_used.len()).sum();
+        if shown == 0 {
+            0.0
+        } else {
+            used as f64 / shown as f64
+        }
+    }
+}
+
+pub const QUALITY_WINDOW: usize = 10;
+pub const QUALITY_MIN_HELPFUL_RATIO: f64 = 0.4;
+pub const QUALITY_LOW_WINDOWS: usize = 3;
+
+/// Tracks response feedback in consecutive windows of `QUALITY_WINDOW`
+/// ratings; quality counts as degrading once `QUALITY_LOW_WINDOWS` windows in
+/// a row had a helpful ratio below `QUALITY_MIN_HELPFUL_RATIO`.
+#[derive(Debug, Clone, Default)]
+pub struct QualityMonitor {
+    helpful: usize,
+    rated: usize,
+    low_windows: usize,
+}
+
+impl QualityMonitor {
+    /// Records one rating. Returns true when this rating is the one that
+    /// tipped the monitor into the degrading state.
+    pub fn update(&mut self, helpful: bool) -> bool {
+        let was_degrading = self.is_degrading();
+        self.rated += 1;
+        if helpful {
+            self.helpful += 1;
+        }
+        if self.rated == QUALITY_WINDOW {
+            let ratio = self.helpful as f64 / self.rated as f64;
+            self.low_windows = if ratio < QUALITY_MIN_HELPFUL_RATIO {
+                self.low_windows + 1
+            } else {
+                0
+            };
+            self.helpful = 0;
+            self.rated = 0;
+        }
+        !was_degrading && self.is_degrading()
+    }
+
+    pub fn is_degrading(&self) -> bool {
+        self.low_windows >= QUALITY_LOW_WINDOWS
+    }
+}
+
+pub const SUCCESS_RATE_WINDOW: usize = 20;
+pub const SUCCESS_RATE_BATCH: usize = 10;
+pub const SUCCESS_RATE_MIN: f64 = 0.5;
+pub const SUCCESS_RATE_LOW_BATCHES: usize = 2;
+
+/// `success` of the last `SUCCESS_RATE_WINDOW` generated trajectories. The
+/// alarm is raised once `SUCCESS_RATE_LOW_BATCHES` consecutive batches of
+/// `SUCCESS_RATE_BATCH` succeeded less than `SUCCESS_RATE_MIN` of the time.
+#[derive(Debug, Clone, Default)]
+pub struct SuccessRateWindow {
+    recent: VecDeque<bool>,
+    batch_successes: usize,
+    batch_len: usize,
+    low_batches: usize,
+}
+
+impl SuccessRateWindow {
+    /// Records one outcome. Returns true when this outcome is the one that
+    /// raised the alarm.
+    pub fn update(&mut self, success: bool) -> bool {
+        let was_alarmed = self.is_alarmed();
+        if self.recent.len() == SUCCESS_RATE_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(success);
+        self.batch_len += 1;
+        if success {
+            self.batch_successes += 1;
+        }
+        if self.batch_len == SUCCESS_RATE_BATCH {
+            let rate = self.batch_successes as f64 / self.batch_len as f64;
+            self.low_batches = if rate < SUCCESS_RATE_MIN {
+                self.low_batches + 1
+            } else {
+                0
+            };
+            self.batch_successes = 0;
+            self.batch_len = 0;
+        }
+        !was_alarmed && self.is_alarmed()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recent.is_empty()
+    }
+
+    /// Fraction of the window that succeeded; 1.0 before any trajectory.
+    pub fn rate(&self) -> f64 {
+        if self.recent.is_empty() {
+            return 1.0;
+        }
+        self.recent.iter().filter(|success| **success).count() as f64 / self.recent.len() as f64
+    }
+
+    pub fn is_alarmed(&self) -> bool {
+        self.low_batches >= SUCCESS_RATE_LOW_BATCHES
+    }
+}
+
+/// Calls and total time per tool name since startup.
+#[derive(Debug, Clone, Default)]
+pub struct ToolUsage {
+    invocations: HashMap<String, u64>,
+    total_ms: HashMap<String, f64>,
+}
+
+impl ToolUsage {
+    pub fn record(&mut self, tool: &str, elapsed: Duration) {
+        *self.invocations.entry(tool.to_string()).or_default() += 1;
+        *self.total_ms.entry(tool.to_string()).or_default() += elapsed.as_secs_f64() * 1000.0;
+    }
+
+    pub fn invocations(&self) -> HashMap<String, u64> {
+        self.invocations.clone()
+    }
+
+    /// Mean milliseconds per call, per tool.
+    pub fn avg_latency_ms(&self) -> HashMap<String, f64> {
+        self.invocations
+            .iter()
+            .map(|(tool, calls)| (tool.clone(), self.total_ms[tool] / *calls as f64))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffToken {
+    Same(String),
+    Inserted(String),
+    Deleted(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimilarityMethod {
+    Jaccard,
+    Cosine,
+    BM25,
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicatePair {
+    pub bullet_a_id: String,
+    pub bullet_b_id: String,
+    pub similarity: f64,
+    pub method: SimilarityMethod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryClass {
+    Factual,
+    Creative,
+    Code,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveTemperature {
+    pub factual_temperature: f64,
+    pub creative_temperature: f64,
+    pub code_temperature: f64,
+}
+
+impl AdaptiveTemperature {
+    pub fn temperature_for(&self, class: QueryClass) -> f64 {
+        match class {
+            QueryClass::Factual => self.factual_temperature,
+            QueryClass::Creative => self.creative_temperature,
+            QueryClass::Code => self.code_temperature,
+        }
+    }
+}
+
+impl Default for AdaptiveTemperature {
+    fn default() -> Self {
+        Self {
+            factual_temperature: 0.2,
+            creative_temperature: 0.9,
+            code_temperature: 0.3,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PullProgress {
+    pub status: String,
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ModelCapabilities {
+    pub supports_thinking: bool,
+    pub supports_structured_output: bool,
+    pub max_context: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub context_length: i32,
+    pub parameter_count: String,
+    pub architecture: String,
+    pub capabilities: ModelCapabilities,
+}
+
+/// Which LLM backends answered during `ACEFramework::initialize`.
+#[derive(Debug, Clone, Default)]
+pub struct InitResult {
+    pub backends_ready: Vec<String>,
+    pub backends_unavailable: Vec<String>,
+}
+
+impl InitResult {
+    pub fn is_ready(&self, backend: &str) -> bool {
+        self.backends_ready.iter().any(|b| b == backend)
+    }
+}
+
+/// Where an `ACEFramework` is in its life. Queries are only served while
+/// `Ready` or `Degraded`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleState {
+    Created,
+    Initializing,
+    Ready,
+    /// Initialized, but the listed parts are unavailable.
+    Degraded(Vec<String>),
+    ShuttingDown,
+    Shutdown,
+}
+
+impl LifecycleState {
+    pub fn is_serving(&self) -> bool {
+        matches!(self, LifecycleState::Ready | LifecycleState::Degraded(_))
+    }
+}
+
+/// How long `ACEFramework::initialize` keeps polling the backend before giving up.
+#[derive(Debug, Clone)]
+pub struct InitConfig {
+    pub max_attempts: u32,
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+    pub auto_pull: bool,
+}
+
+impl InitConfig {
+    pub fn single_attempt() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for InitConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            poll_interval: Duration::from_secs(2),
+            timeout: Duration::from_secs(30),
+            auto_pull: false,
+        }
+    }
+}
+
+/// Per-call overrides; unset fields fall back to `OllamaConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct GenerationOptions {
+    pub temperature: Option<f64>,
+    pub enable_thinking: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +674,14 @@ pub struct OllamaConfig {
     pub temperature: f64,
     pub max_tokens: i32,
     pub context_window: i32,
+    pub force_response_language: Option<String>,
+    pub adaptive_temperature: Option<AdaptiveTemperature>,
+    /// Ollama model used by `/api/embed`; `None` leaves embedding to the
+    /// local ONNX model.
+    pub embed_model: Option<String>,
+    /// Answer every generation with `dry_run_response` instead of calling
+    /// the server.
+    pub dry_run: bool,
 }
 
 impl Default for OllamaConfig {
@@ -71,6 +692,10 @@ impl Default for OllamaConfig {
             temperature: 0.7,
             max_tokens: 512,
             context_window: 2048,
+            force_response_language: None,
+            adaptive_temperature: None,
+            embed_model: None,
+            dry_run: false,
         }
     }
 }
@@ -79,7 +704,442 @@ impl ContextState {
     pub fn new() -> Self {
         Self {
             bullets: HashMap::new(),
-            version: 0,
+            version: VectorClock::default(),
+        }
+    }
+}
+
+/// What telemetry the user agreed to. Every telemetry call site must check
+/// this at runtime; compiling the `telemetry` feature is not consent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConsent {
+    pub local_metrics: bool,
+    pub remote_tracing: bool,
+    pub usage_analytics: bool,
+}
+
+impl Default for TelemetryConsent {
+    fn default() -> Self {
+        Self {
+            local_metrics: true,
+            remote_tracing: false,
+            usage_analytics: false,
+        }
+    }
+}
+
+impl TelemetryConsent {
+    /// Anything leaving the machine additionally requires `usage_analytics`.
+    pub fn allows_remote_tracing(&self) -> bool {
+        self.remote_tracing && self.usage_analytics
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InteractiveConfig {
+    /// Short command -> command it expands to, both with or without the
+    /// leading `/` (e.g. `r = "research"`, `myresearch = "/research"`).
+    pub aliases: HashMap<String, String>,
+}
+
+impl InteractiveConfig {
+    pub fn default_aliases() -> HashMap<String, String> {
+        [("r", "research"), ("t", "think"), ("s", "search")]
+            .iter()
+            .map(|(alias, command)| (alias.to_string(), command.to_string()))
+            .collect()
+    }
+}
+
+impl Default for InteractiveConfig {
+    fn default() -> Self {
+        Self {
+            aliases: Self::default_aliases(),
+        }
+    }
+}
+
+/// Every icon printed by the terminal UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IconKey {
+    Robot,
+    User,
+    Test,
+    Search,
+    Web,
+    Context,
+    Thinking,
+    Research,
+    Stats,
+    Growth,
+    Learned,
+    Duplicate,
+    Help,
+    Pin,
+    Tasks,
+    Window,
+    Image,
+    Info,
+    Success,
+    Error,
+    Warning,
+    Debug,
+    Link,
+    Offline,
+    Question,
+    Done,
+    Reasoning,
+    Insight,
+    Note,
+    Refine,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum IconSet {
+    #[default]
+    Emoji,
+    Ascii,
+    /// Keys missing from the map fall back to the ASCII icon.
+    Custom(HashMap<IconKey, String>),
+}
+
+impl IconSet {
+    fn emoji(key: IconKey) -> &'static str {
+        match key {
+            IconKey::Robot => "🤖",
+            IconKey::User => "👤",
+            IconKey::Test => "🧪",
+            IconKey::Search => "🔍",
+            IconKey::Web => "🌐",
+            IconKey::Context => "📚",
+            IconKey::Thinking => "🧠",
+            IconKey::Research => "🔬",
+            IconKey::Stats => "📊",
+            IconKey::Growth => "📈",
+            IconKey::Learned => "💡",
+            IconKey::Duplicate => "🔁",
+            IconKey::Help => "📖",
+            IconKey::Pin => "📌",
+            IconKey::Tasks => "📋",
+            IconKey::Window => "🪟",
+            // These three render one column narrower than their width, so they
+            // carry the extra space themselves.
+            IconKey::Image => "🖼️ ",
+            IconKey::Info => "ℹ️ ",
+            IconKey::Warning => "⚠️ ",
+            IconKey::Success => "✅",
+            IconKey::Error => "❌",
+            IconKey::Debug => "🐛",
+            IconKey::Link => "🔗",
+            IconKey::Offline => "📴",
+            IconKey::Question => "❓",
+            IconKey::Done => "✓",
+            IconKey::Reasoning => "🤔",
+            IconKey::Insight => "💡",
+            IconKey::Note => "📝",
+            IconKey::Refine => "🔁",
+        }
+    }
+
+    fn ascii(key: IconKey) -> &'static str {
+        match key {
+            IconKey::Robot => "[ACE]",
+            IconKey::User => "[YOU]",
+            IconKey::Test => "[TEST]",
+            IconKey::Search => "[SEARCH]",
+            IconKey::Web => "[WEB]",
+            IconKey::Context => "[CTX]",
+            IconKey::Thinking => "[THINK]",
+            IconKey::Research => "[RESEARCH]",
+            IconKey::Stats => "[STATS]",
+            IconKey::Growth => "[+]",
+            IconKey::Learned => "[LEARN]",
+            IconKey::Duplicate => "[DUP]",
+            IconKey::Help => "[HELP]",
+            IconKey::Pin => "[PIN]",
+            IconKey::Tasks => "[TASKS]",
+            IconKey::Window => "[WINDOW]",
+            IconKey::Image => "[IMAGE]",
+            IconKey::Info => "[INFO]",
+            IconKey::Success => "[OK]",
+            IconKey::Error => "[ERR]",
+            IconKey::Warning => "[WARN]",
+            IconKey::Debug => "[DEBUG]",
+            IconKey::Link => "[LINK]",
+            IconKey::Offline => "[OFFLINE]",
+            IconKey::Question => "[?]",
+            IconKey::Done => "[DONE]",
+            IconKey::Reasoning => "[REASON]",
+            IconKey::Insight => "[IDEA]",
+            IconKey::Note => "[NOTE]",
+            IconKey::Refine => "[REFINE]",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DisplayConfig {
+    /// When false every icon is ASCII, whatever `icon_set` says.
+    pub emoji_enabled: bool,
+    pub icon_set: IconSet,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            emoji_enabled: true,
+            icon_set: IconSet::Emoji,
+        }
+    }
+}
+
+impl DisplayConfig {
+    pub fn ascii() -> Self {
+        Self {
+            emoji_enabled: false,
+            icon_set: IconSet::Ascii,
+        }
+    }
+
+    pub fn icon(&self, key: IconKey) -> &str {
+        if !self.emoji_enabled {
+            return IconSet::ascii(key);
+        }
+        match &self.icon_set {
+            IconSet::Emoji => IconSet::emoji(key),
+            IconSet::Ascii => IconSet::ascii(key),
+            IconSet::Custom(icons) => icons.get(&key).map(String::as_str).unwrap_or(IconSet::ascii(key)),
         }
     }
 }
+
+static DISPLAY_CONFIG: std::sync::OnceLock<DisplayConfig> = std::sync::OnceLock::new();
+
+/// Sets the process-wide display config. Only the first call has any effect,
+/// and it must happen before anything is printed to take effect everywhere.
+pub fn set_display_config(config: DisplayConfig) {
+    let _ = DISPLAY_CONFIG.set(config);
+}
+
+pub fn display_config() -> &'static DisplayConfig {
+    DISPLAY_CONFIG.get_or_init(DisplayConfig::default)
+}
+
+/// Icon for `key` under the process-wide display config.
+pub fn icon(key: IconKey) -> &'static str {
+    display_config().icon(key)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line: timestamp, level, target, message.
+    Json,
+}
+
+/// `dry_run = true` in ace.toml, or `--dry-run`: the LLM is never called,
+/// web searches come back empty and nothing is written to disk, so commands
+/// and pipelines can be previewed without side effects or costs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct DryRunMode(pub bool);
+
+impl DryRunMode {
+    pub fn is_enabled(&self) -> bool {
+        self.0
+    }
+}
+
+/// A custom `/name` interactive command backed by a shell script.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandPlugin {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Regex the arguments must fully match; empty accepts anything.
+    #[serde(default)]
+    pub args_pattern: String,
+    /// `sh -c` template; `{query}` and `{session_id}` are replaced with
+    /// shell-quoted values.
+    pub script: String,
+}
+
+/// Settings read from `ace.toml`; every section is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub interactive: InteractiveConfig,
+    /// `None` when ace.toml has no `[telemetry]` table, i.e. the user has not
+    /// made a choice yet and should see the notice.
+    pub telemetry: Option<TelemetryConsent>,
+    /// JSONL file of curated bullets loaded (and pinned) at startup.
+    pub warm_from_seed: Option<std::path::PathBuf>,
+    /// Plaintext fallback for keys not in the system keychain.
+    pub api_keys: HashMap<String, String>,
+    /// Ollama model to use instead of the built-in default.
+    pub model: Option<String>,
+    /// Ollama model for `/api/embed`, see `OllamaConfig::embed_model`.
+    pub embed_model: Option<String>,
+    pub session_name: Option<String>,
+    /// Written by the onboarding wizard once setup is done.
+    pub first_run_complete: bool,
+    pub log_format: LogFormat,
+    /// Log lines are also appended here, in `log_format`.
+    pub log_file: Option<std::path::PathBuf>,
+    /// `[[commands]]` plugins run arbitrary shell commands, so they are
+    /// ignored unless this is set.
+    pub enable_command_plugins: bool,
+    pub commands: Vec<CommandPlugin>,
+    pub dry_run: DryRunMode,
+    pub sync: SyncConfig,
+    /// Rate every answer with the reflector model, see
+    /// `ACEFramework::process_quality_check`.
+    pub quality_check_enabled: bool,
+}
+
+/// `[sync]` in ace.toml: pull from `remote` whenever its manifest changes.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub remote: Option<String>,
+    /// POSTed a small JSON event each time a change is detected.
+    pub webhook: Option<String>,
+}
+
+impl AppConfig {
+    pub fn telemetry_consent(&self) -> TelemetryConsent {
+        self.telemetry.clone().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub files_scanned: usize,
+    pub files_skipped: usize,
+    pub bullets_created: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IngestReport {
+    pub chunks_processed: usize,
+    pub bullets_created: usize,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub id: String,
+    pub content_hash: u64,
+    pub version: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncManifest {
+    pub entries: Vec<ManifestEntry>,
+    #[serde(default)]
+    pub clock: VectorClock,
+}
+
+/// Bullets to download from a remote, and how many differ in ways that
+/// cannot be resolved by taking the remote copy.
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan {
+    pub fetch: Vec<String>,
+    pub conflicts: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    pub added: usize,
+    pub updated: usize,
+    pub conflicts: usize,
+}
+
+/// Image for vision models; `data` is base64 without a `data:` URL prefix.
+#[derive(Debug, Clone)]
+pub struct Base64Image {
+    pub data: String,
+    pub media_type: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultimodalPrompt {
+    pub text: String,
+    pub images: Vec<Base64Image>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quality_monitor_trips_after_three_unhelpful_windows() {
+        let mut monitor = QualityMonitor::default();
+        let tripped: Vec<usize> = (0..3 * QUALITY_WINDOW).filter(|_| monitor.update(false)).collect();
+
+        assert_eq!(tripped, vec![3 * QUALITY_WINDOW - 1]);
+        assert!(monitor.is_degrading());
+        assert!(!monitor.update(false), "only the tipping rating reports the trip");
+    }
+
+    #[test]
+    fn quality_monitor_resets_after_a_healthy_window() {
+        let mut monitor = QualityMonitor::default();
+        for _ in 0..2 * QUALITY_WINDOW {
+            monitor.update(false);
+        }
+        for _ in 0..QUALITY_WINDOW {
+            monitor.update(true);
+        }
+        for _ in 0..2 * QUALITY_WINDOW {
+            assert!(!monitor.update(false));
+        }
+        assert!(!monitor.is_degrading());
+    }
+
+    /// Feeds one batch with `successes` successful trajectories first.
+    fn batch(window: &mut SuccessRateWindow, successes: usize) -> bool {
+        let mut tripped = false;
+        for i in 0..SUCCESS_RATE_BATCH {
+            tripped |= window.update(i < successes);
+        }
+        tripped
+    }
+
+    #[test]
+    fn success_rate_alarm_needs_two_low_batches() {
+        let mut window = SuccessRateWindow::default();
+        assert!(!batch(&mut window, 2));
+        assert!(!window.is_alarmed());
+
+        assert!(batch(&mut window, 3));
+        assert!(window.is_alarmed());
+        assert!(!batch(&mut window, 0), "the alarm is only reported once");
+    }
+
+    #[test]
+    fn success_rate_alarm_resets_after_a_good_batch() {
+        let mut window = SuccessRateWindow::default();
+        batch(&mut window, 0);
+        batch(&mut window, SUCCESS_RATE_BATCH);
+        assert!(!batch(&mut window, 0));
+        assert!(!window.is_alarmed());
+    }
+
+    #[test]
+    fn success_rate_covers_the_last_twenty() {
+        let mut window = SuccessRateWindow::default();
+        assert!(window.is_empty());
+        assert_eq!(window.rate(), 1.0);
+
+        batch(&mut window, 0);
+        batch(&mut window, SUCCESS_RATE_BATCH);
+        assert_eq!(window.rate(), 0.5);
+        batch(&mut window, SUCCESS_RATE_BATCH);
+        assert_eq!(window.rate(), 1.0);
+    }
+}